@@ -1,12 +1,18 @@
 use std::{
-    collections::{HashMap, HashSet},
+    borrow::Cow,
+    cmp::Ordering,
+    collections::BTreeMap,
     error::Error,
+    ffi::OsString,
     fs::File,
-    io::{BufRead, BufReader},
+    io::{BufRead, BufReader, BufWriter, Write},
     path::{Path, PathBuf},
 };
 
+use caseless::Caseless;
 use clap::{App, Arg};
+use serde::Serialize;
+use unicode_normalization::UnicodeNormalization;
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Input {
@@ -15,14 +21,285 @@ pub enum Input {
     SecondStdin(PathBuf),
 }
 
+/// How the order of the inputs is checked, like GNU comm does.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CheckOrder {
+    /// Warn about the first unsorted line of each file once there's a line that couldn't be
+    /// paired, and fail at the end.
+    #[default]
+    Default,
+    /// `--check-order`, fail at the first unsorted line.
+    Check,
+    /// `--nocheck-order`
+    NoCheck,
+}
+
+/// A collation table, where every line of the file lists characters that sort the same, from the
+/// first to the last. `#` starts a comment.
+///
+/// Characters that aren't in the table sort after the ones that are, in code point order, and
+/// lines that are the same by the table are ordered by their code points so that only identical
+/// lines pair.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CollationTable {
+    weights: BTreeMap<char, u32>,
+}
+
+impl CollationTable {
+    pub fn parse(table: &str) -> Result<Self, Box<dyn Error>> {
+        let mut weights = BTreeMap::new();
+
+        let rows = table
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or_default())
+            .filter(|line| !line.trim().is_empty());
+
+        for (weight, row) in rows.enumerate() {
+            for character in row.split_whitespace() {
+                let mut chars = character.chars();
+
+                let (Some(character), None) = (chars.next(), chars.next()) else {
+                    return Err(format!("\"{character}\" is not a single character").into());
+                };
+
+                if weights.insert(character, weight as u32).is_some() {
+                    return Err(format!("\"{character}\" is in the table twice").into());
+                }
+            }
+        }
+
+        Ok(Self { weights })
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let table = std::fs::read_to_string(&path)
+            .map_err(|err| format!("{}: {err}", path.as_ref().display()))?;
+
+        Self::parse(&table).map_err(|err| format!("{}: {err}", path.as_ref().display()).into())
+    }
+
+    fn weight(&self, character: char) -> u32 {
+        self.weights
+            .get(&character)
+            .copied()
+            .unwrap_or(self.weights.len() as u32 + character as u32)
+    }
+}
+
+/// How the lines are compared.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Collation {
+    /// By the raw bytes, like `LC_ALL=C comm`.
+    #[default]
+    Byte,
+    /// By the Unicode code points, bytes that aren't UTF-8 compare as U+FFFD.
+    CodePoint,
+    Table(CollationTable),
+}
+
+impl Collation {
+    pub fn compare(&self, first: &[u8], second: &[u8]) -> Ordering {
+        match self {
+            Collation::Byte => first.cmp(second),
+            Collation::CodePoint => String::from_utf8_lossy(first)
+                .chars()
+                .cmp(String::from_utf8_lossy(second).chars()),
+            Collation::Table(table) => {
+                let first = String::from_utf8_lossy(first);
+                let second = String::from_utf8_lossy(second);
+
+                first
+                    .chars()
+                    .map(|x| table.weight(x))
+                    .cmp(second.chars().map(|x| table.weight(x)))
+                    .then_with(|| first.chars().cmp(second.chars()))
+            }
+        }
+    }
+}
+
+/// Folds the case of the line for `-i` with the full Unicode case folding, so `Straße` and
+/// `STRASSE` are the same. The line is normalized first, and the dot above that `İ` leaves on the
+/// `i` is dropped so that `İstanbul` and `istanbul` are the same too.
+pub fn fold_case(line: &[u8]) -> Vec<u8> {
+    let line = String::from_utf8_lossy(line);
+    let mut folded = String::with_capacity(line.len());
+
+    for character in line.nfd().default_case_fold().nfd() {
+        if character == '\u{307}' && folded.ends_with(['i', 'j']) {
+            continue;
+        }
+        folded.push(character);
+    }
+
+    folded.nfc().collect::<String>().into_bytes()
+}
+
+/// How the lines are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Format {
+    /// The three columns of GNU comm.
+    #[default]
+    Text,
+    /// A `{"line": ..., "property": ...}` object per line.
+    Json,
+    /// A `line,property` record per line, after a header.
+    Csv,
+}
+
+/// Compares the lines by a field rather than the whole line, like `join`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Key {
+    pub separator: String,
+    /// The 1-based key field of file 1 and file 2.
+    pub fields: [usize; 2],
+}
+
+impl Key {
+    fn split<'a>(&self, line: &'a [u8]) -> Vec<&'a [u8]> {
+        let separator = self.separator.as_bytes();
+        let mut fields = Vec::new();
+        let mut start = 0;
+        let mut index = 0;
+
+        while index + separator.len() <= line.len() {
+            if line[index..].starts_with(separator) {
+                fields.push(&line[start..index]);
+                index += separator.len();
+                start = index;
+            } else {
+                index += 1;
+            }
+        }
+        fields.push(&line[start..]);
+
+        fields
+    }
+
+    /// The key of the line from file `file_number`, empty when the line doesn't have the field.
+    fn field<'a>(&self, line: &'a [u8], file_number: usize) -> &'a [u8] {
+        self.split(line)
+            .get(self.fields[file_number - 1] - 1)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// The fields of the two lines, besides the keys, that differ, numbered like in file 1.
+    fn changed_fields(&self, first: &[u8], second: &[u8], insensitive: bool) -> Vec<usize> {
+        let mut first = self.split(first);
+        let mut second = self.split(second);
+        let [first_key, second_key] = self.fields.map(|x| x - 1);
+
+        if first_key < first.len() {
+            first.remove(first_key);
+        }
+        if second_key < second.len() {
+            second.remove(second_key);
+        }
+
+        let same = |x: &[u8], y: &[u8]| {
+            if insensitive {
+                fold_case(x) == fold_case(y)
+            } else {
+                x == y
+            }
+        };
+
+        (0..first.len().max(second.len()))
+            .filter(|&x| match (first.get(x), second.get(x)) {
+                (Some(first), Some(second)) => !same(first, second),
+                _ => true,
+            })
+            .map(|x| if x < first_key { x + 1 } else { x + 2 })
+            .collect()
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Config {
     pub input: Input,
     pub show_col1: bool,
     pub show_col2: bool,
     pub show_col3: bool,
+    /// The changed lines, when comparing by `key`.
+    pub show_col4: bool,
     pub insensitive: bool,
     pub delimiter: String,
+    pub check_order: CheckOrder,
+    pub collation: Collation,
+    pub total: bool,
+    /// Lines end with NUL rather than a newline, in the inputs and the output.
+    pub zero_terminated: bool,
+    pub format: Format,
+    /// Whether `delimiter` was given rather than the default, CSV uses a comma otherwise.
+    pub explicit_delimiter: bool,
+    pub key: Option<Key>,
+}
+
+/// The options of `from_args` that take a value, short and long, after which an argument like
+/// `-1k` is a value rather than an option.
+const SHORT_VALUE_OPTIONS: [char; 3] = ['d', 't', 'k'];
+const LONG_VALUE_OPTIONS: [&str; 10] = [
+    "output-delimiter",
+    "order",
+    "collation",
+    "format",
+    "field-separator",
+    "key",
+    "key1",
+    "key2",
+    "1k",
+    "2k",
+];
+
+/// Turns `-1k FIELD` and `-2k FIELD` into `--key1 FIELD` and `--key2 FIELD`, which clap would
+/// otherwise read as `-1 -k FIELD` and `-2 -k FIELD`.
+fn expand_key_options(args: impl IntoIterator<Item = OsString>) -> Vec<OsString> {
+    let mut args = args.into_iter();
+    // the program name
+    let mut result = args.next().into_iter().collect::<Vec<_>>();
+
+    while let Some(arg) = args.next() {
+        let text = arg.to_str().unwrap_or_default();
+
+        if text == "--" {
+            // the rest are files
+            result.push(arg);
+            result.extend(args);
+            break;
+        }
+
+        let long = match text.get(..3) {
+            Some("-1k") => "--key1",
+            Some("-2k") => "--key2",
+            _ => {
+                let takes_value = match text.strip_prefix("--") {
+                    Some(name) => LONG_VALUE_OPTIONS.contains(&name),
+                    // in a cluster like "-zd", the first option that takes a value takes the rest
+                    // of the cluster, or the next argument when it's the last one
+                    None => {
+                        text.starts_with('-')
+                            && text.find(SHORT_VALUE_OPTIONS) == Some(text.len() - 1)
+                    }
+                };
+                result.push(arg);
+                if takes_value {
+                    result.extend(args.next());
+                }
+                continue;
+            }
+        };
+
+        match &text[3..] {
+            "" => {
+                result.push(long.into());
+                result.extend(args.next());
+            }
+            field => result.push(format!("{long}={field}").into()),
+        }
+    }
+
+    result
 }
 
 impl Config {
@@ -60,6 +337,11 @@ impl Config {
                     .short("3")
                     .help("Supress printing of column 3"),
             )
+            .arg(
+                Arg::with_name("4")
+                    .short("4")
+                    .help("Supress printing of column 4, the changed lines when comparing by key"),
+            )
             .arg(
                 Arg::with_name("case-insensitive")
                     .short("i")
@@ -73,7 +355,121 @@ impl Config {
                     .help("Output delimiter")
                     .default_value("\t"),
             )
-            .get_matches();
+            .arg(
+                Arg::with_name("check-order")
+                    .long("check-order")
+                    .overrides_with("nocheck-order")
+                    .help(
+                        "Check that the input is correctly sorted, even if all lines are pairable",
+                    ),
+            )
+            .arg(
+                Arg::with_name("nocheck-order")
+                    .long("nocheck-order")
+                    .overrides_with("check-order")
+                    .help("Do not check that the input is correctly sorted"),
+            )
+            .arg(
+                Arg::with_name("order")
+                    .long("order")
+                    .value_name("ORDER")
+                    .takes_value(true)
+                    .possible_values(&["byte", "codepoint"])
+                    .default_value("byte")
+                    .help("Compare the lines by their bytes or their Unicode code points"),
+            )
+            .arg(
+                Arg::with_name("collation")
+                    .long("collation")
+                    .value_name("FILE")
+                    .takes_value(true)
+                    .help("Compare the lines with the collation table in FILE"),
+            )
+            .arg(
+                Arg::with_name("total")
+                    .long("total")
+                    .help("Output a summary with the number of lines in each column"),
+            )
+            .arg(
+                Arg::with_name("zero-terminated")
+                    .short("z")
+                    .long("zero-terminated")
+                    .help("Line delimiter is NUL, not newline"),
+            )
+            .arg(
+                Arg::with_name("format")
+                    .long("format")
+                    .value_name("FORMAT")
+                    .takes_value(true)
+                    .possible_values(&["text", "json", "csv"])
+                    .default_value("text")
+                    .help("Output the columns as text, or a record per line as JSON or CSV, where the output delimiter separates the fields"),
+            )
+            .arg(
+                Arg::with_name("field-separator")
+                    .short("t")
+                    .long("field-separator")
+                    .value_name("DELIM")
+                    .takes_value(true)
+                    .help("Field separator of the inputs when comparing by key [default: ,]"),
+            )
+            .arg(
+                Arg::with_name("key")
+                    .short("k")
+                    .long("key")
+                    .value_name("FIELD")
+                    .takes_value(true)
+                    .help("Compare the lines by FIELD of both files"),
+            )
+            .arg(
+                Arg::with_name("key1")
+                    .long("key1")
+                    .alias("1k")
+                    .value_name("FIELD")
+                    .takes_value(true)
+                    .help("Compare the lines by FIELD of file 1, also given as -1k FIELD"),
+            )
+            .arg(
+                Arg::with_name("key2")
+                    .long("key2")
+                    .alias("2k")
+                    .value_name("FIELD")
+                    .takes_value(true)
+                    .help("Compare the lines by FIELD of file 2, also given as -2k FIELD"),
+            )
+            .get_matches_from(expand_key_options(std::env::args_os()));
+
+        let parse_field = |name: &str| -> Result<Option<usize>, Box<dyn Error>> {
+            match matches.value_of(name) {
+                Some(field) => match field.parse() {
+                    Ok(field) if field > 0 => Ok(Some(field)),
+                    _ => Err(format!("invalid field number: \"{field}\"").into()),
+                },
+                None => Ok(None),
+            }
+        };
+        let both = parse_field("key")?;
+        let key = match (parse_field("key1")?.or(both), parse_field("key2")?.or(both)) {
+            (None, None) => None,
+            (first, second) => Some(Key {
+                separator: matches
+                    .value_of("field-separator")
+                    .unwrap_or(",")
+                    .to_string(),
+                fields: [first.unwrap_or(1), second.unwrap_or(1)],
+            }),
+        };
+
+        if key.as_ref().is_some_and(|x| x.separator.is_empty()) {
+            return Err("the field separator can't be empty".into());
+        }
+
+        // the columns of a CSV record couldn't be told apart
+        if matches.value_of("format") == Some("csv")
+            && matches.value_of("output-delimiter") == Some("")
+        {
+            return Err("the output delimiter can't be empty with --format csv".into());
+        }
 
         Ok(Config {
             input: match (
@@ -88,11 +484,33 @@ impl Config {
             show_col1: !matches.is_present("1"),
             show_col2: !matches.is_present("2"),
             show_col3: !matches.is_present("3"),
+            show_col4: !matches.is_present("4"),
             insensitive: matches.is_present("case-insensitive"),
             delimiter: matches
                 .value_of("output-delimiter")
                 .expect("should at least has tab as a default value")
                 .to_string(),
+            check_order: if matches.is_present("check-order") {
+                CheckOrder::Check
+            } else if matches.is_present("nocheck-order") {
+                CheckOrder::NoCheck
+            } else {
+                CheckOrder::Default
+            },
+            collation: match (matches.value_of("collation"), matches.value_of("order")) {
+                (Some(table), _) => Collation::Table(CollationTable::load(table)?),
+                (None, Some("codepoint")) => Collation::CodePoint,
+                (None, _) => Collation::Byte,
+            },
+            total: matches.is_present("total"),
+            zero_terminated: matches.is_present("zero-terminated"),
+            format: match matches.value_of("format") {
+                Some("json") => Format::Json,
+                Some("csv") => Format::Csv,
+                _ => Format::Text,
+            },
+            explicit_delimiter: matches.occurrences_of("output-delimiter") > 0,
+            key,
         })
     }
 }
@@ -109,99 +527,439 @@ fn open<P: AsRef<Path>>(input: Option<P>) -> Result<Box<dyn BufRead>, Box<dyn Er
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
 pub enum Property {
     FirstExclusive,
     SecondExclusive,
     Mutual,
+    /// The keys are the same but some of the other fields aren't.
+    Changed,
 }
 
-pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    let (first_file, second_file) = match config.input {
-        Input::BothFile(first_file, second_file) => {
-            (open(Some(first_file))?, open(Some(second_file))?)
+/// A line of one of the inputs, and what it's compared by.
+struct Line {
+    text: Vec<u8>,
+    /// The key field, folded for `-i`, or the whole line folded.
+    key: Option<Vec<u8>>,
+}
+
+impl Line {
+    fn key(&self) -> &[u8] {
+        self.key.as_deref().unwrap_or(&self.text)
+    }
+}
+
+/// One of the sorted inputs, read a line at a time.
+struct SortedLines {
+    file: Box<dyn BufRead>,
+    /// 1 or 2, for the messages.
+    file_number: usize,
+    line_number: usize,
+    current: Option<Line>,
+    /// Whether a line out of order was reported already.
+    disordered: bool,
+}
+
+impl SortedLines {
+    fn new(
+        file: Box<dyn BufRead>,
+        file_number: usize,
+        config: &Config,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut lines = Self {
+            file,
+            file_number,
+            line_number: 0,
+            current: None,
+            disordered: false,
+        };
+        lines.current = lines.read(config)?;
+
+        Ok(lines)
+    }
+
+    fn read(&mut self, config: &Config) -> std::io::Result<Option<Line>> {
+        let mut text = Vec::new();
+        let terminator = if config.zero_terminated { b'\0' } else { b'\n' };
+
+        if self.file.read_until(terminator, &mut text)? == 0 {
+            return Ok(None);
         }
-        Input::FirstStdin(second_file) => {
-            (open(Option::<PathBuf>::None)?, open(Some(second_file))?)
+        self.line_number += 1;
+
+        if text.last() == Some(&terminator) {
+            text.pop();
+
+            if !config.zero_terminated && text.ends_with(b"\r") {
+                text.pop();
+            }
         }
-        Input::SecondStdin(first_file) => (open(Some(first_file))?, open(Option::<PathBuf>::None)?),
-    };
 
-    let first_lines = first_file
-        .lines()
-        .map(|x| {
-            x.map(|mut x| {
-                if config.insensitive {
-                    x.make_ascii_lowercase();
-                    x
+        let key = match &config.key {
+            Some(key) => {
+                let field = key.field(&text, self.file_number);
+
+                Some(if config.insensitive {
+                    fold_case(field)
                 } else {
-                    x
+                    field.to_vec()
+                })
+            }
+            None => config.insensitive.then(|| fold_case(&text)),
+        };
+
+        Ok(Some(Line { text, key }))
+    }
+
+    /// Moves to the next line, and returns whether it sorts before the previous one.
+    fn advance(&mut self, config: &Config) -> std::io::Result<bool> {
+        let next = self.read(config)?;
+        let previous = std::mem::replace(&mut self.current, next);
+
+        Ok(match (previous, &self.current) {
+            (Some(previous), Some(current)) => {
+                config.collation.compare(current.key(), previous.key()) == Ordering::Less
+            }
+            _ => false,
+        })
+    }
+}
+
+/// Merges the two sorted inputs like GNU comm, calling `emit` with every line and the column it
+/// belongs to as soon as it's known. The lines are read lazily and the duplicates are kept: a line
+/// that's twice in the first file and once in the second is both mutual and exclusive to the
+/// first.
+///
+/// The lines are compared by `config.collation`, after folding their case with [`fold_case`] for
+/// `-i`, but they're emitted as they are in the input.
+///
+/// With `config.key`, only the key fields are compared, and lines with the same key whose other
+/// fields differ are [`Property::Changed`]. They're emitted with the line of file 2 and the numbers
+/// of the fields that differ, which is empty for everything else.
+pub fn merge(
+    first_file: Box<dyn BufRead>,
+    second_file: Box<dyn BufRead>,
+    config: &Config,
+    mut emit: impl FnMut(&[u8], Property, &[usize]) -> std::io::Result<()>,
+) -> Result<(), Box<dyn Error>> {
+    let mut files = [
+        SortedLines::new(first_file, 1, config)?,
+        SortedLines::new(second_file, 2, config)?,
+    ];
+
+    // GNU comm only warns about the order once there's a line that couldn't be paired
+    let mut unpairable = false;
+    let mut disordered = false;
+
+    loop {
+        let (property, advance) = match (&files[0].current, &files[1].current) {
+            (None, None) => break,
+            (Some(_), None) => (Property::FirstExclusive, [true, false]),
+            (None, Some(_)) => (Property::SecondExclusive, [false, true]),
+            (Some(first), Some(second)) => {
+                match config.collation.compare(first.key(), second.key()) {
+                    Ordering::Less => (Property::FirstExclusive, [true, false]),
+                    Ordering::Greater => (Property::SecondExclusive, [false, true]),
+                    Ordering::Equal => (Property::Mutual, [true, true]),
                 }
-            })
+            }
+        };
+
+        let mut property = property;
+        let mut changed = Vec::new();
+
+        if let (Property::Mutual, Some(key), Some(first), Some(second)) =
+            (property, &config.key, &files[0].current, &files[1].current)
+        {
+            changed = key.changed_fields(&first.text, &second.text, config.insensitive);
+
+            if !changed.is_empty() {
+                property = Property::Changed;
+            }
+        }
+
+        let line = match property {
+            Property::SecondExclusive | Property::Changed => &files[1].current,
+            Property::FirstExclusive | Property::Mutual => &files[0].current,
+        };
+        emit(
+            &line.as_ref().expect("the line exists").text,
+            property,
+            &changed,
+        )?;
+
+        unpairable |= matches!(
+            property,
+            Property::FirstExclusive | Property::SecondExclusive
+        );
+
+        for (file, advance) in files.iter_mut().zip(advance) {
+            if !advance || !file.advance(config)? || file.disordered {
+                continue;
+            }
+
+            let message = format!(
+                "file {} is not in sorted order at line {}",
+                file.file_number, file.line_number
+            );
+
+            match config.check_order {
+                CheckOrder::Check => return Err(message.into()),
+                CheckOrder::Default if unpairable => {
+                    eprintln!("{message}");
+                    file.disordered = true;
+                    disordered = true;
+                }
+                CheckOrder::Default | CheckOrder::NoCheck => {}
+            }
+        }
+    }
+
+    if disordered {
+        return Err("input is not in sorted order".into());
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct Record<'a> {
+    line: Cow<'a, str>,
+    property: Property,
+    #[serde(skip_serializing_if = "<[usize]>::is_empty")]
+    fields: &'a [usize],
+}
+
+#[derive(Serialize)]
+struct Total {
+    total: BTreeMap<Property, usize>,
+}
+
+/// Quotes the CSV field if it has to be.
+fn write_csv_field(output: &mut impl Write, field: &[u8], delimiter: &[u8]) -> std::io::Result<()> {
+    let quoted = field.contains(&b'"')
+        || field.contains(&b'\n')
+        || field.contains(&b'\r')
+        || field.windows(delimiter.len()).any(|x| x == delimiter);
+
+    if !quoted {
+        return output.write_all(field);
+    }
+
+    output.write_all(b"\"")?;
+    for part in field.split_inclusive(|x| *x == b'"') {
+        output.write_all(part)?;
+
+        if part.ends_with(b"\"") {
+            output.write_all(b"\"")?;
+        }
+    }
+    output.write_all(b"\"")
+}
+
+fn join_fields(fields: &[usize]) -> String {
+    fields
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Writes the merged lines in the configured format, keeping the count of each column for
+/// `--total`.
+struct Printer<'a, W> {
+    output: W,
+    config: &'a Config,
+    counts: BTreeMap<Property, usize>,
+}
+
+impl<'a, W: Write> Printer<'a, W> {
+    fn new(output: W, config: &'a Config) -> std::io::Result<Self> {
+        let mut printer = Self {
+            output,
+            config,
+            counts: [
+                Property::FirstExclusive,
+                Property::SecondExclusive,
+                Property::Mutual,
+                Property::Changed,
+            ]
+            .into_iter()
+            .filter(|x| *x != Property::Changed || config.key.is_some())
+            .map(|x| (x, 0))
+            .collect(),
+        };
+
+        if config.format == Format::Csv {
+            let delimiter = printer.csv_delimiter().to_owned();
+
+            printer.output.write_all(b"line")?;
+            printer.output.write_all(delimiter.as_bytes())?;
+            printer.output.write_all(b"property")?;
+
+            if config.key.is_some() {
+                printer.output.write_all(delimiter.as_bytes())?;
+                printer.output.write_all(b"fields")?;
+            }
+            printer.end_line()?;
+        }
+
+        Ok(printer)
+    }
+
+    fn csv_delimiter(&self) -> &str {
+        if self.config.explicit_delimiter {
+            &self.config.delimiter
+        } else {
+            ","
+        }
+    }
+
+    fn end_line(&mut self) -> std::io::Result<()> {
+        self.output.write_all(if self.config.zero_terminated {
+            b"\0"
+        } else {
+            b"\n"
         })
-        .collect::<Result<HashSet<_>, _>>()?;
-    let seoncd_lines = second_file
-        .lines()
-        .map(|x| {
-            x.map(|mut x| {
-                if config.insensitive {
-                    x.make_ascii_lowercase();
-                    x
+    }
+
+    /// Changed lines are written in a fourth column in text, followed by the changed fields
+    /// separated by commas, and the fields are another field in JSON and CSV.
+    fn print(&mut self, line: &[u8], property: Property, fields: &[usize]) -> std::io::Result<()> {
+        *self.counts.entry(property).or_default() += 1;
+
+        let config = self.config;
+        let shown = match property {
+            Property::FirstExclusive => config.show_col1,
+            Property::SecondExclusive => config.show_col2,
+            Property::Mutual => config.show_col3,
+            Property::Changed => config.show_col4,
+        };
+
+        if !shown {
+            return Ok(());
+        }
+
+        match config.format {
+            Format::Text => {
+                let col1 = if config.show_col1 {
+                    config.delimiter.as_str()
+                } else {
+                    ""
+                };
+                let col2 = if config.show_col2 {
+                    config.delimiter.as_str()
+                } else {
+                    ""
+                };
+                let col3 = if config.show_col3 {
+                    config.delimiter.as_str()
                 } else {
-                    x
+                    ""
+                };
+
+                match property {
+                    Property::FirstExclusive => {}
+                    Property::SecondExclusive => self.output.write_all(col1.as_bytes())?,
+                    Property::Mutual => write!(self.output, "{col1}{col2}")?,
+                    Property::Changed => write!(self.output, "{col1}{col2}{col3}")?,
+                }
+                self.output.write_all(line)?;
+
+                if property == Property::Changed {
+                    write!(self.output, "{}{}", config.delimiter, join_fields(fields))?;
+                }
+            }
+            Format::Json => {
+                let record = Record {
+                    line: String::from_utf8_lossy(line),
+                    property,
+                    fields,
+                };
+
+                serde_json::to_writer(&mut self.output, &record)?;
+            }
+            Format::Csv => {
+                let delimiter = self.csv_delimiter().to_owned();
+                let property = format!("{property:?}");
+
+                write_csv_field(&mut self.output, line, delimiter.as_bytes())?;
+                self.output.write_all(delimiter.as_bytes())?;
+                write_csv_field(&mut self.output, property.as_bytes(), delimiter.as_bytes())?;
+
+                if config.key.is_some() {
+                    let fields = join_fields(fields);
+
+                    self.output.write_all(delimiter.as_bytes())?;
+                    write_csv_field(&mut self.output, fields.as_bytes(), delimiter.as_bytes())?;
                 }
-            })
-        })
-        .collect::<Result<HashSet<_>, _>>()?;
-
-    let first_exclusive = first_lines
-        .difference(&seoncd_lines)
-        .map(|x| (x, Property::FirstExclusive));
-    let second_exclusive = seoncd_lines
-        .difference(&first_lines)
-        .map(|x| (x, Property::SecondExclusive));
-    let mutual = first_lines
-        .intersection(&seoncd_lines)
-        .map(|x| (x, Property::Mutual));
-    let mut representation = first_exclusive
-        .chain(second_exclusive)
-        .chain(mutual)
-        .collect::<Vec<_>>();
-    representation.sort_by(|(a, _), (b, _)| a.cmp(b));
-
-    for (line, prop) in representation {
-        match prop {
-            Property::FirstExclusive if config.show_col1 => {
-                println!("{line}")
-            }
-            Property::SecondExclusive if config.show_col2 => {
-                println!(
-                    "{}{line}",
-                    if config.show_col1 {
-                        &config.delimiter
-                    } else {
-                        ""
-                    }
-                )
-            }
-            Property::Mutual if config.show_col3 => {
-                println!(
-                    "{}{}{line}",
-                    if config.show_col1 {
-                        &config.delimiter
-                    } else {
-                        ""
-                    },
-                    if config.show_col2 {
-                        &config.delimiter
-                    } else {
-                        ""
-                    }
-                )
             }
-            _ => {}
         }
+
+        self.end_line()
     }
 
-    Ok(())
+    /// Writes the `--total` line, which counts the lines of every column, even the suppressed
+    /// ones, like GNU comm.
+    fn finish(mut self) -> std::io::Result<()> {
+        if self.config.total {
+            match self.config.format {
+                Format::Text | Format::Csv => {
+                    let delimiter = match self.config.format {
+                        Format::Csv => self.csv_delimiter(),
+                        _ => self.config.delimiter.as_str(),
+                    };
+                    let counts = self.counts.values().map(ToString::to_string);
+                    let total = counts
+                        .chain(std::iter::once("total".to_string()))
+                        .collect::<Vec<_>>()
+                        .join(delimiter);
+
+                    self.output.write_all(total.as_bytes())?;
+                }
+                Format::Json => {
+                    let total = Total {
+                        total: std::mem::take(&mut self.counts),
+                    };
+
+                    serde_json::to_writer(&mut self.output, &total)?;
+                }
+            }
+
+            self.end_line()?;
+        }
+
+        self.output.flush()
+    }
+}
+
+pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
+    let (first_file, second_file) = match &config.input {
+        Input::BothFile(first_file, second_file) => {
+            (open(Some(first_file))?, open(Some(second_file))?)
+        }
+        Input::FirstStdin(second_file) => {
+            (open(Option::<PathBuf>::None)?, open(Some(second_file))?)
+        }
+        Input::SecondStdin(first_file) => (open(Some(first_file))?, open(Option::<PathBuf>::None)?),
+    };
+
+    let stdout = std::io::stdout();
+    let mut printer = Printer::new(BufWriter::new(stdout.lock()), &config)?;
+
+    let merged = merge(
+        first_file,
+        second_file,
+        &config,
+        |line, property, fields| printer.print(line, property, fields),
+    );
+
+    // what's printed before an error is still written, the total only when there's no error
+    match merged {
+        Ok(()) => Ok(printer.finish()?),
+        Err(err) => {
+            printer.output.flush()?;
+            Err(err)
+        }
+    }
 }
@@ -1,3 +1,6 @@
+// the tests pass their arguments as `&[..]`, which newer versions of clippy flag
+#![allow(clippy::needless_borrows_for_generic_args)]
+
 use assert_cmd::Command;
 use predicates::prelude::*;
 use rand::{distributions::Alphanumeric, Rng};
@@ -7,7 +10,18 @@ const PRG: &str = "commr";
 const EMPTY: &str = "tests/inputs/empty.txt";
 const FILE1: &str = "tests/inputs/file1.txt";
 const FILE2: &str = "tests/inputs/file2.txt";
+const FILE3: &str = "tests/inputs/file3.txt";
 const BLANK: &str = "tests/inputs/blank.txt";
+const CITIES1: &str = "tests/inputs/cities1.txt";
+const CITIES2: &str = "tests/inputs/cities2.txt";
+const CITIES3: &str = "tests/inputs/cities3.txt";
+const CITIES3_UPPER: &str = "tests/inputs/cities3_upper.txt";
+const CITIES_SV1: &str = "tests/inputs/cities_sv1.txt";
+const CITIES_SV2: &str = "tests/inputs/cities_sv2.txt";
+const SWEDISH: &str = "tests/inputs/swedish.collation";
+const EXPORT1: &str = "tests/inputs/export1.csv";
+const EXPORT2: &str = "tests/inputs/export2.csv";
+const EXPORT3: &str = "tests/inputs/export3.csv";
 
 type TestResult = Result<(), Box<dyn std::error::Error>>;
 
@@ -345,3 +359,268 @@ fn blank_file1() -> TestResult {
 //fn file1_blanks() -> TestResult {
 //    run(&[FILE1, BLANKS], "tests/expected/file1_blanks.out")
 //}
+
+//============================================================================
+#[test]
+fn file1_file3_duplicates() -> TestResult {
+    run(&[FILE1, FILE3], "tests/expected/file1_file3.out")
+}
+
+//============================================================================
+fn run_unsorted(args: &[&str], expected_file: &str, messages: &[&str]) -> TestResult {
+    let expected = fs::read_to_string(expected_file)?;
+    let mut assert = Command::cargo_bin(PRG)?
+        .args(args)
+        .args([CITIES1, CITIES2])
+        .assert()
+        .failure()
+        .stdout(expected);
+
+    for message in messages {
+        assert = assert.stderr(predicate::str::contains(*message));
+    }
+    Ok(())
+}
+
+//============================================================================
+#[test]
+fn cities1_cities2_unsorted() -> TestResult {
+    run_unsorted(
+        &[],
+        "tests/expected/cities1_cities2.out",
+        &[
+            "file 1 is not in sorted order at line 2",
+            "file 2 is not in sorted order at line 2",
+            "input is not in sorted order",
+        ],
+    )
+}
+
+//============================================================================
+#[test]
+fn cities1_cities2_check_order() -> TestResult {
+    run_unsorted(
+        &["--check-order"],
+        "tests/expected/cities1_cities2.check.out",
+        &["file 1 is not in sorted order at line 2"],
+    )?;
+
+    Command::cargo_bin(PRG)?
+        .args(["--check-order", FILE1, FILE3])
+        .assert()
+        .success()
+        .stderr("");
+    Ok(())
+}
+
+//============================================================================
+#[test]
+fn cities1_cities2_nocheck_order() -> TestResult {
+    let expected = fs::read_to_string("tests/expected/cities1_cities2.nocheck.out")?;
+    Command::cargo_bin(PRG)?
+        .args(["--check-order", "--nocheck-order", CITIES1, CITIES2])
+        .assert()
+        .success()
+        .stdout(expected)
+        .stderr("");
+    Ok(())
+}
+
+//============================================================================
+#[test]
+fn cities3_cities3_upper_unicode_insensitive() -> TestResult {
+    run(
+        &["-i", CITIES3, CITIES3_UPPER],
+        "tests/expected/cities3_cities3_upper.i.out",
+    )?;
+    run(
+        &["-i", CITIES3_UPPER, CITIES3],
+        "tests/expected/cities3_upper_cities3.i.out",
+    )?;
+    run(
+        &["--nocheck-order", CITIES3, CITIES3_UPPER],
+        "tests/expected/cities3_cities3_upper.out",
+    )
+}
+
+//============================================================================
+#[test]
+fn cities_sv_collation() -> TestResult {
+    run(
+        &["--collation", SWEDISH, CITIES_SV1, CITIES_SV2],
+        "tests/expected/cities_sv1_cities_sv2.collation.out",
+    )?;
+
+    Command::cargo_bin(PRG)?
+        .args([CITIES_SV1, CITIES_SV2])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "file 1 is not in sorted order at line 4",
+        ));
+    Ok(())
+}
+
+//============================================================================
+#[test]
+fn file1_file2_codepoint() -> TestResult {
+    run(
+        &["--order", "codepoint", FILE1, FILE2],
+        "tests/expected/file1_file2.out",
+    )
+}
+
+//============================================================================
+#[test]
+fn dies_bad_collation() -> TestResult {
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args(["--collation", &bad, FILE1, FILE2])
+        .assert()
+        .failure()
+        .stderr(predicate::str::is_match(format!(
+            "{bad}: .* [(]os error 2[)]"
+        ))?);
+
+    Command::cargo_bin(PRG)?
+        .args(["--collation", FILE3, FILE1, FILE2])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("is in the table twice"));
+    Ok(())
+}
+
+//============================================================================
+#[test]
+fn file1_file2_total() -> TestResult {
+    run(
+        &["--total", FILE1, FILE2],
+        "tests/expected/file1_file2.total.out",
+    )?;
+    run(
+        &["--total", "-12", "-d", ":", FILE1, FILE3],
+        "tests/expected/file1_file3.12.total.delim.out",
+    )
+}
+
+//============================================================================
+#[test]
+fn zero1_zero2_zero_terminated() -> TestResult {
+    run(
+        &[
+            "-z",
+            "--total",
+            "tests/inputs/zero1.txt",
+            "tests/inputs/zero2.txt",
+        ],
+        "tests/expected/zero1_zero2.total.out",
+    )
+}
+
+//============================================================================
+#[test]
+fn file1_file2_json() -> TestResult {
+    run(
+        &["--format", "json", "--total", "-2", FILE1, FILE2],
+        "tests/expected/file1_file2.2.total.json",
+    )
+}
+
+//============================================================================
+#[test]
+fn file1_file2_csv() -> TestResult {
+    run(
+        &["--format", "csv", "--total", FILE1, FILE2],
+        "tests/expected/file1_file2.total.csv",
+    )?;
+    run(
+        &["--format", "csv", "-3", "-d", ";", FILE1, FILE3],
+        "tests/expected/file1_file3.3.delim.csv",
+    )
+}
+
+//============================================================================
+#[test]
+fn export1_export2_key() -> TestResult {
+    run(
+        &["-k", "1", "--total", EXPORT1, EXPORT2],
+        "tests/expected/export1_export2.k1.total.out",
+    )?;
+    run(
+        &["-k", "1", "--format", "json", "-4", EXPORT1, EXPORT2],
+        "tests/expected/export1_export2.k1.4.json",
+    )?;
+    run(
+        &["-k", "1", "--format", "csv", "-12", EXPORT1, EXPORT2],
+        "tests/expected/export1_export2.k1.12.csv",
+    )
+}
+
+//============================================================================
+#[test]
+fn export1_export3_separate_keys() -> TestResult {
+    run(
+        &["--1k", "1", "--2k", "2", EXPORT1, EXPORT3],
+        "tests/expected/export1_export3.1k1.2k2.out",
+    )
+}
+
+//============================================================================
+#[test]
+fn export1_export3_short_separate_keys() -> TestResult {
+    // "-1k 1" is the key of file 1 rather than "-1 -k 1"
+    run(
+        &["-1k", "1", "-2k", "2", EXPORT1, EXPORT3],
+        "tests/expected/export1_export3.1k1.2k2.out",
+    )?;
+    run(
+        &["-1k1", "-2k2", EXPORT1, EXPORT3],
+        "tests/expected/export1_export3.1k1.2k2.out",
+    )?;
+    Command::cargo_bin(PRG)?
+        .args(["-1", "-k", "1", EXPORT1, EXPORT3])
+        .assert()
+        .success()
+        .stdout("Alice,1,bangkok\nBob,2,Chiang Mai\nEve,5,Krabi\n");
+    Ok(())
+}
+
+//============================================================================
+#[test]
+fn export_key_separator() -> TestResult {
+    let input = fs::read_to_string(EXPORT1)?.replace(',', ";");
+    Command::cargo_bin(PRG)?
+        .args(["-t", ";", "-k", "1", "-i", "-", "tests/inputs/export2.ssv"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(
+            "\t\t1;Alice;Bangkok\n\t\t\t2;Bob;Khon Kaen\t3\n3;Carol;Phuket\n\
+             \t4;Dave;Hat Yai\n\t\t5;Eve;Krabi\n",
+        );
+    Ok(())
+}
+
+//============================================================================
+#[test]
+fn dies_bad_key() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-k", "0", EXPORT1, EXPORT2])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid field number: \"0\""));
+    Ok(())
+}
+
+//============================================================================
+#[test]
+fn dies_empty_csv_delimiter() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--format", "csv", "-d", "", FILE1, FILE2])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "the output delimiter can't be empty with --format csv",
+        ));
+    Ok(())
+}
@@ -1,4 +1,12 @@
-use std::{error::Error, fmt::format, ops::Range, path::PathBuf};
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    error::Error,
+    fs::File,
+    io::{BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use clap::{App, Arg};
 
@@ -14,24 +22,107 @@ pub enum DisplayMode {
     Lines,
 }
 
+/// How `-f` keeps track of the files it follows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Follow {
+    /// `--follow=descriptor`, keeps reading the file that was opened, even after it's renamed.
+    Descriptor,
+    /// `--follow=name` or `-F`, reopens the file when its name points to another one, like after a
+    /// log rotation, and waits for it when it's missing.
+    Name,
+}
+
+/// What ends the lines, or records, that are counted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Separator {
+    /// A LF only.
+    Lf,
+    /// A LF, a CRLF or a lone CR.
+    #[default]
+    Universal,
+    /// `-z`, a NUL.
+    Nul,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Config {
     pub files: Vec<PathBuf>,
     pub take_value: TakeValue,
     pub display_mode: DisplayMode,
     pub no_header: bool,
+    pub follow: Option<Follow>,
+    /// Stop following once the process exits.
+    pub pid: Option<i32>,
+    /// How long to wait between the checks for new data when following.
+    pub sleep_interval: Duration,
+    pub separator: Separator,
 }
 
-fn parse_take_value(str: &str) -> Result<TakeValue, Box<dyn Error>> {
-    if str == "+0" {
-        return Ok(TakeValue::PlusZero);
+/// The multiplier of a size suffix, like GNU tail: `b` is 512, `kB`, `MB`... are powers of 1000,
+/// and `K`, `KiB`, `M`, `MiB`... are powers of 1024. `k` and `m` are the same as `K` and `M`.
+fn parse_multiplier(suffix: &str) -> Option<u128> {
+    const PREFIXES: &str = "KMGTPEZYRQ";
+
+    if suffix.is_empty() {
+        return Some(1);
+    }
+    if suffix == "b" {
+        return Some(512);
     }
 
-    if str.as_bytes().first() == Some(&b'+') {
-        Ok(TakeValue::TakeNum(str[1..].parse::<i64>()?))
-    } else {
-        Ok(TakeValue::TakeNum(-str.parse::<i64>()?))
+    let mut chars = suffix.chars();
+    let prefix = chars.next()?;
+    let exponent = PREFIXES.find(prefix.to_ascii_uppercase())? as u32 + 1;
+
+    // `k` and `m` are the only prefixes that are also lowercase
+    if prefix.is_ascii_lowercase() && !matches!(prefix, 'k' | 'm') {
+        return None;
+    }
+
+    let base: u128 = match chars.as_str() {
+        "" | "iB" => 1024,
+        "B" => 1000,
+        _ => return None,
+    };
+
+    // 1000^10 and 1024^10 don't fit in u128, but they're clamped just the same
+    Some(base.checked_pow(exponent).unwrap_or(u128::MAX))
+}
+
+/// Parses the count of `-n` and `-c`: `N` or `-N` for the last N, `+N` to start from the Nth, with
+/// an optional size suffix. Counts that don't fit in an `i64` are clamped, so the last of them is
+/// everything.
+fn parse_take_value(str: &str) -> Result<TakeValue, Box<dyn Error>> {
+    let (plus, rest) = match str.as_bytes().first() {
+        Some(b'+') => (true, &str[1..]),
+        Some(b'-') => (false, &str[1..]),
+        _ => (false, str),
+    };
+
+    let digits = rest
+        .find(|x: char| !x.is_ascii_digit())
+        .unwrap_or(rest.len());
+    let (number, suffix) = rest.split_at(digits);
+
+    if number.is_empty() {
+        return Err(format!("invalid number: '{str}'").into());
     }
+
+    let multiplier =
+        parse_multiplier(suffix).ok_or_else(|| format!("invalid suffix: '{suffix}'"))?;
+    let count = number
+        .bytes()
+        .try_fold(0u128, |count, digit| {
+            count.checked_mul(10)?.checked_add(u128::from(digit - b'0'))
+        })
+        .and_then(|count| count.checked_mul(multiplier))
+        .map_or(i64::MAX, |count| i64::try_from(count).unwrap_or(i64::MAX));
+
+    Ok(match (plus, count) {
+        (true, 0) => TakeValue::PlusZero,
+        (true, count) => TakeValue::TakeNum(count),
+        (false, count) => TakeValue::TakeNum(-count),
+    })
 }
 
 impl Config {
@@ -71,6 +162,52 @@ impl Config {
                     .takes_value(true)
                     .help("Number of lines"),
             )
+            .arg(
+                Arg::with_name("follow")
+                    .value_name("HOW")
+                    .short("f")
+                    .long("follow")
+                    .takes_value(true)
+                    .min_values(0)
+                    .require_equals(true)
+                    .possible_values(&["descriptor", "name"])
+                    .help("Output appended data as the file grows, HOW is descriptor by default"),
+            )
+            .arg(
+                Arg::with_name("follow-name")
+                    .short("F")
+                    .help("Same as --follow=name, and keep trying to open the missing files"),
+            )
+            .arg(
+                Arg::with_name("pid")
+                    .value_name("PID")
+                    .long("pid")
+                    .takes_value(true)
+                    .help("With -f, terminate after process ID, PID dies"),
+            )
+            .arg(
+                Arg::with_name("sleep-interval")
+                    .value_name("SECONDS")
+                    .short("s")
+                    .long("sleep-interval")
+                    .takes_value(true)
+                    .help("With -f, sleep for approximately SECONDS between iterations [default: 1.0]"),
+            )
+            .arg(
+                Arg::with_name("zero-terminated")
+                    .short("z")
+                    .long("zero-terminated")
+                    .conflicts_with("separator")
+                    .help("Line delimiter is NUL, not newline"),
+            )
+            .arg(
+                Arg::with_name("separator")
+                    .value_name("SEPARATOR")
+                    .long("separator")
+                    .takes_value(true)
+                    .possible_values(&["lf", "universal", "nul"])
+                    .help("What ends the lines: LF only, LF, CRLF or CR, or NUL [default: universal]"),
+            )
             .get_matches();
 
         let (display_mode, take_value) =
@@ -96,120 +233,640 @@ impl Config {
             display_mode,
             take_value,
             no_header: matches.is_present("quiet"),
+            follow: if matches.is_present("follow-name") {
+                Some(Follow::Name)
+            } else if matches.is_present("follow") {
+                match matches.value_of("follow") {
+                    Some("name") => Some(Follow::Name),
+                    _ => Some(Follow::Descriptor),
+                }
+            } else {
+                None
+            },
+            pid: matches
+                .value_of("pid")
+                .map(|str| {
+                    str.parse::<i32>()
+                        .ok()
+                        .filter(|pid| *pid > 0)
+                        .ok_or_else(|| format!("invalid PID: '{str}'"))
+                })
+                .transpose()?,
+            sleep_interval: match matches.value_of("sleep-interval") {
+                Some(str) => str
+                    .parse::<f64>()
+                    .ok()
+                    .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+                    .ok_or_else(|| format!("invalid number of seconds: '{str}'"))?,
+                None => Duration::from_secs(1),
+            },
+            separator: match matches.value_of("separator") {
+                _ if matches.is_present("zero-terminated") => Separator::Nul,
+                Some("lf") => Separator::Lf,
+                Some("nul") => Separator::Nul,
+                _ => Separator::Universal,
+            },
         })
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
-struct Metadata {
-    line_count: usize,
-    byte_count: usize,
+/// How much of the blocks are read at a time, backwards from the end of a file or from a stream.
+const BLOCK_SIZE: usize = 64 * 1024;
+
+/// Where the output starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Start {
+    /// `+N`, after skipping that many lines or bytes.
+    Skip(u64),
+    /// The last lines or bytes.
+    Last(u64),
+}
+
+impl From<TakeValue> for Start {
+    fn from(take_value: TakeValue) -> Self {
+        match take_value {
+            // `+0` and `+1` both start from the first line, like GNU tail
+            TakeValue::PlusZero => Start::Skip(0),
+            TakeValue::TakeNum(positive) if positive > 0 => Start::Skip(positive as u64 - 1),
+            TakeValue::TakeNum(negative) => Start::Last(negative.unsigned_abs()),
+        }
+    }
 }
 
-impl Metadata {
-    fn get(str: &str) -> Self {
-        Self {
-            line_count: str.lines().count(),
-            byte_count: str.len(),
+impl Separator {
+    /// Whether the byte at `index` ends a line. `next` is the byte after the buffer.
+    fn is_terminator(self, buffer: &[u8], index: usize, next: Option<u8>) -> bool {
+        match (self, buffer[index]) {
+            (Separator::Lf | Separator::Universal, b'\n') | (Separator::Nul, b'\0') => true,
+            (Separator::Universal, b'\r') => buffer.get(index + 1).copied().or(next) != Some(b'\n'),
+            _ => false,
+        }
+    }
+
+    /// How much of the buffer is complete lines. A CR at the end isn't, it may be followed by a
+    /// LF.
+    fn complete_length(self, buffer: &[u8]) -> usize {
+        (0..buffer.len())
+            .rev()
+            .find(|&index| {
+                self.is_terminator(buffer, index, None)
+                    && !(index + 1 == buffer.len() && buffer[index] == b'\r')
+            })
+            .map_or(0, |index| index + 1)
+    }
+
+    /// Reads a line and its terminator into `line`, and returns how many bytes it read.
+    fn read_line(self, reader: &mut impl BufRead, line: &mut Vec<u8>) -> std::io::Result<usize> {
+        match self {
+            Separator::Lf => return reader.read_until(b'\n', line),
+            Separator::Nul => return reader.read_until(b'\0', line),
+            Separator::Universal => {}
+        }
+
+        let start = line.len();
+        // a CR at the end of the buffer, that's a CRLF if the next one starts with a LF
+        let mut pending_cr = false;
+
+        loop {
+            let buffer = reader.fill_buf()?;
+
+            if buffer.is_empty() {
+                break;
+            }
+
+            if pending_cr {
+                if buffer[0] == b'\n' {
+                    line.push(b'\n');
+                    reader.consume(1);
+                }
+                break;
+            }
+
+            match buffer.iter().position(|x| matches!(x, b'\n' | b'\r')) {
+                Some(index) if index + 1 == buffer.len() && buffer[index] == b'\r' => {
+                    let length = buffer.len();
+
+                    line.extend_from_slice(buffer);
+                    reader.consume(length);
+                    pending_cr = true;
+                }
+                Some(index) => {
+                    let end = if buffer[index] == b'\r' && buffer[index + 1] == b'\n' {
+                        index + 2
+                    } else {
+                        index + 1
+                    };
+
+                    line.extend_from_slice(&buffer[..end]);
+                    reader.consume(end);
+                    break;
+                }
+                None => {
+                    let length = buffer.len();
+
+                    line.extend_from_slice(buffer);
+                    reader.consume(length);
+                }
+            }
         }
+
+        Ok(line.len() - start)
     }
 }
 
-fn parse_take_value_to_range(take_value: TakeValue, upper_bound: usize) -> Range<usize> {
-    match take_value {
-        TakeValue::PlusZero => 0..upper_bound,
-        TakeValue::TakeNum(positive) if positive >= 0 => {
-            ((positive as usize).min(upper_bound))..upper_bound
+/// Skips `count` lines or bytes from the start of the reader.
+fn skip(reader: &mut impl BufRead, count: u64, config: &Config) -> std::io::Result<()> {
+    match config.display_mode {
+        DisplayMode::Bytes => {
+            std::io::copy(&mut reader.take(count), &mut std::io::sink())?;
         }
-        TakeValue::TakeNum(negative) => {
-            (upper_bound.saturating_add_signed(negative as isize))..upper_bound
+        DisplayMode::Lines => {
+            let mut line = Vec::new();
+
+            for _ in 0..count {
+                line.clear();
+
+                if config.separator.read_line(reader, &mut line)? == 0 {
+                    break;
+                }
+            }
         }
     }
+
+    Ok(())
 }
 
-fn get_bytes(str: &str, take_value: TakeValue) -> String {
-    let range = parse_take_value_to_range(take_value, str.len());
-    String::from_utf8_lossy(&str.as_bytes()[range]).to_string()
+/// Finds where the last `count` lines of the file start, reading it backwards a block at a time
+/// until there are enough line terminators.
+fn find_last_lines(file: &mut File, count: u64, separator: Separator) -> std::io::Result<u64> {
+    let length = file.seek(SeekFrom::End(0))?;
+
+    if count == 0 {
+        return Ok(length);
+    }
+
+    let mut buffer = vec![0; BLOCK_SIZE];
+    let mut found = 0;
+    let mut end = length;
+    let mut next = None;
+
+    while end > 0 {
+        let start = end.saturating_sub(BLOCK_SIZE as u64);
+        let block = &mut buffer[..(end - start) as usize];
+
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(block)?;
+
+        for index in (0..block.len()).rev() {
+            let position = start + index as u64;
+
+            // the terminator of the last line doesn't start another one
+            if position + 1 == length || !separator.is_terminator(block, index, next) {
+                continue;
+            }
+
+            found += 1;
+            if found == count {
+                return Ok(position + 1);
+            }
+        }
+
+        next = block.first().copied();
+        end = start;
+    }
+
+    Ok(0)
 }
 
-fn get_line_byte_positions(text: &str) -> Vec<Range<usize>> {
-    let mut current_position = 0;
-    let mut results = Vec::new();
+/// Writes the tail of a regular file, seeking to where it starts rather than reading all of it.
+/// Returns where the file ended.
+fn tail_file(file: &mut File, config: &Config, output: &mut impl Write) -> std::io::Result<u64> {
+    let start = match (Start::from(config.take_value), config.display_mode) {
+        (Start::Skip(count), DisplayMode::Bytes) => count,
+        (Start::Skip(count), DisplayMode::Lines) => {
+            let mut reader = BufReader::new(&mut *file);
 
-    let mut skip = false;
+            skip(&mut reader, count, config)?;
+            std::io::copy(&mut reader, output)?;
 
-    for (byte, char) in text.char_indices() {
-        if skip {
-            skip = false;
-            continue;
+            return file.stream_position();
+        }
+        (Start::Last(count), DisplayMode::Bytes) => {
+            file.seek(SeekFrom::End(0))?.saturating_sub(count)
+        }
+        (Start::Last(count), DisplayMode::Lines) => find_last_lines(file, count, config.separator)?,
+    };
+
+    file.seek(SeekFrom::Start(start))?;
+    std::io::copy(file, output)?;
+
+    file.stream_position()
+}
+
+/// Writes the tail of input that can't seek, like a pipe, keeping only the last lines or bytes
+/// while reading it.
+fn tail_stream(
+    mut reader: impl BufRead,
+    config: &Config,
+    output: &mut impl Write,
+) -> std::io::Result<()> {
+    let count = match Start::from(config.take_value) {
+        Start::Skip(count) => {
+            skip(&mut reader, count, config)?;
+            std::io::copy(&mut reader, output)?;
+
+            return Ok(());
         }
+        Start::Last(count) => usize::try_from(count).unwrap_or(usize::MAX),
+    };
+
+    match config.display_mode {
+        DisplayMode::Bytes => {
+            let mut last = VecDeque::new();
+            let mut buffer = vec![0; BLOCK_SIZE];
 
-        // ordinary lf
-        if char == '\n' {
-            #[allow(clippy::range_plus_one)]
-            results.push(current_position..byte + 1);
+            loop {
+                let read = reader.read(&mut buffer)?;
 
-            current_position = byte + 1;
+                if read == 0 {
+                    break;
+                }
+
+                last.extend(&buffer[..read]);
+                last.drain(..last.len().saturating_sub(count));
+            }
+
+            let (first, second) = last.as_slices();
+            output.write_all(first)?;
+            output.write_all(second)?;
         }
+        DisplayMode::Lines => {
+            let mut last = VecDeque::new();
+            let mut line = Vec::new();
 
-        // crlf
-        if char == '\r' {
-            if text.as_bytes().get(byte + 1) == Some(&b'\n') {
-                #[allow(clippy::range_plus_one)]
-                results.push(current_position..byte + 2);
+            while config.separator.read_line(&mut reader, &mut line)? > 0 {
+                if count == 0 {
+                    line.clear();
+                    continue;
+                }
 
-                current_position = byte + 2;
+                last.push_back(line);
 
-                skip = true;
-            } else {
-                #[allow(clippy::range_plus_one)]
-                results.push(current_position..byte + 1);
+                // the line that's dropped is reused for the next one
+                line = if last.len() > count {
+                    last.pop_front().unwrap_or_default()
+                } else {
+                    Vec::new()
+                };
+                line.clear();
+            }
+
+            for line in last {
+                output.write_all(&line)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes the output as UTF-8, replacing what isn't with U+FFFD like `String::from_utf8_lossy`.
+struct LossyWriter<W: Write> {
+    inner: W,
+    /// The start of a character that may continue in the next write.
+    pending: Vec<u8>,
+}
+
+impl<W: Write> LossyWriter<W> {
+    fn finish(&mut self) -> std::io::Result<()> {
+        if !self.pending.is_empty() {
+            self.pending.clear();
+            self.inner
+                .write_all(char::REPLACEMENT_CHARACTER.to_string().as_bytes())?;
+        }
+
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Write for LossyWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+
+        let mut rest = &self.pending[..];
+        loop {
+            match std::str::from_utf8(rest) {
+                Ok(valid) => {
+                    self.inner.write_all(valid.as_bytes())?;
+                    rest = &[];
+                    break;
+                }
+                Err(err) => {
+                    let (valid, invalid) = rest.split_at(err.valid_up_to());
+                    self.inner.write_all(valid)?;
+
+                    let Some(length) = err.error_len() else {
+                        rest = invalid;
+                        break;
+                    };
+
+                    self.inner
+                        .write_all(char::REPLACEMENT_CHARACTER.to_string().as_bytes())?;
+                    rest = &invalid[length..];
+                }
+            }
+        }
+
+        self.pending = rest.to_vec();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn display_name(path: &Path) -> Cow<'_, str> {
+    if path == Path::new("-") {
+        Cow::Borrowed("standard input")
+    } else {
+        path.to_string_lossy()
+    }
+}
+
+/// Prints the `==> name <==` headers, every time the output switches to another file.
+struct Headers {
+    enabled: bool,
+    current: Option<usize>,
+}
+
+impl Headers {
+    fn print(&mut self, index: usize, file: &Path) {
+        if !self.enabled || self.current == Some(index) {
+            return;
+        }
+
+        if self.current.is_some() {
+            println!();
+        }
+        println!("==> {} <==", display_name(file));
+
+        self.current = Some(index);
+    }
+}
+
+/// A file that's followed with `-f`, and how much of it was printed already.
+struct Followed {
+    /// Of the file in the arguments, for the headers.
+    index: usize,
+    path: PathBuf,
+    file: Option<File>,
+    position: u64,
+    /// The last line, until it's complete.
+    partial: Vec<u8>,
+}
 
-                current_position = byte + 1;
+impl Followed {
+    /// Reads what was appended since the last time, from the start if the file was truncated, and
+    /// returns the lines that are complete, or everything with `flush`.
+    fn read_lines(&mut self, separator: Separator, flush: bool) -> std::io::Result<Vec<u8>> {
+        let mut lines = Vec::new();
+
+        if let Some(file) = &mut self.file {
+            if file.metadata()?.len() < self.position {
+                eprintln!("{}: file truncated", self.path.display());
+                self.position = 0;
+                lines = std::mem::take(&mut self.partial);
             }
+
+            file.seek(SeekFrom::Start(self.position))?;
+            self.position += file.read_to_end(&mut self.partial)? as u64;
+        }
+
+        let complete = if flush {
+            self.partial.len()
+        } else {
+            separator.complete_length(&self.partial)
+        };
+        lines.extend(self.partial.drain(..complete));
+
+        Ok(lines)
+    }
+
+    /// Checks whether the name still points to the file that's followed.
+    fn check_name(&self) -> Name {
+        let metadata = match std::fs::metadata(&self.path) {
+            Ok(metadata) => metadata,
+            Err(err) => return Name::Missing(err),
+        };
+
+        match &self.file {
+            Some(file) => match file.metadata() {
+                Ok(current) if is_same_file(&current, &metadata) => Name::Same,
+                _ => Name::Replaced,
+            },
+            None => Name::Replaced,
         }
     }
 
-    if current_position != text.len() {
-        results.push(current_position..text.len());
+    /// Opens the file that the name points to now, and returns whether it could.
+    fn reopen(&mut self) -> bool {
+        let Ok(file) = File::open(&self.path) else {
+            return false;
+        };
+
+        if self.file.is_some() {
+            eprintln!(
+                "{}: has been replaced; following new file",
+                self.path.display()
+            );
+        } else {
+            eprintln!("{}: has appeared; following new file", self.path.display());
+        }
+
+        self.file = Some(file);
+        self.position = 0;
+
+        true
     }
+}
+
+/// What the name of a file followed with `--follow=name` points to.
+enum Name {
+    Same,
+    Missing(std::io::Error),
+    /// Another file, or a file when there was none.
+    Replaced,
+}
+
+#[cfg(unix)]
+fn is_same_file(first: &std::fs::Metadata, second: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    (first.dev(), first.ino()) == (second.dev(), second.ino())
+}
 
-    results
+#[cfg(not(unix))]
+fn is_same_file(_: &std::fs::Metadata, _: &std::fs::Metadata) -> bool {
+    true
 }
 
-fn get_lines(str: &str, take_value: TakeValue) -> String {
-    let line_ranges = get_line_byte_positions(str);
-    let range = parse_take_value_to_range(take_value, line_ranges.len());
-    dbg!(&line_ranges, &take_value, &range);
-    range
-        .map(|i| &str[line_ranges[i].clone()])
-        .collect::<Vec<&str>>()
-        .join("")
+#[cfg(unix)]
+fn is_alive(pid: i32) -> bool {
+    // SAFETY: the signal 0 only checks whether the process exists
+    let sent = unsafe { libc::kill(pid, 0) } == 0;
+
+    // a process of another user exists too
+    sent || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+fn is_alive(_: i32) -> bool {
+    true
+}
+
+/// Prints what's appended to the files until the process of `--pid` exits, or forever.
+fn follow(config: &Config, mode: Follow, mut files: Vec<Followed>, headers: &mut Headers) {
+    let stdout = std::io::stdout();
+
+    if files.is_empty() {
+        return;
+    }
+
+    loop {
+        // checked before reading, so what the process wrote last is still printed
+        let alive = config.pid.is_none_or(is_alive);
+
+        for file in &mut files {
+            loop {
+                let name = match mode {
+                    Follow::Descriptor => Name::Same,
+                    Follow::Name => file.check_name(),
+                };
+
+                // the last line of a rotated file won't be completed
+                let flush = !alive || matches!(name, Name::Replaced);
+                let lines = match file.read_lines(config.separator, flush) {
+                    Ok(lines) => lines,
+                    Err(err) => {
+                        eprintln!("{}: {err}", file.path.display());
+                        file.file = None;
+                        break;
+                    }
+                };
+
+                if !lines.is_empty() {
+                    headers.print(file.index, &file.path);
+
+                    let mut stdout = stdout.lock();
+                    if stdout
+                        .write_all(&lines)
+                        .and_then(|_| stdout.flush())
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+
+                match name {
+                    Name::Same => break,
+                    Name::Missing(err) => {
+                        if file.file.take().is_some() {
+                            eprintln!("{}: has become inaccessible: {err}", file.path.display());
+                        }
+                        break;
+                    }
+                    // what's left in the rotated file is printed before the new one
+                    Name::Replaced => {
+                        if !file.reopen() {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        if !alive {
+            return;
+        }
+
+        if mode == Follow::Descriptor && files.iter().all(|x| x.file.is_none()) {
+            eprintln!("no files remaining");
+            return;
+        }
+
+        std::thread::sleep(config.sleep_interval);
+    }
 }
 
 pub fn run(config: Config) {
-    let display_header = config.files.len() > 1 && !config.no_header;
+    let mut headers = Headers {
+        enabled: config.files.len() > 1 && !config.no_header,
+        current: None,
+    };
+    let mut followed = Vec::new();
+    let stdout = std::io::stdout();
+
+    for (index, path) in config.files.iter().enumerate() {
+        if path == Path::new("-") {
+            headers.print(index, path);
+
+            let mut output = LossyWriter {
+                inner: stdout.lock(),
+                pending: Vec::new(),
+            };
+            let result = tail_stream(std::io::stdin().lock(), &config, &mut output)
+                .and_then(|_| output.finish());
 
-    for file in config.files {
-        let string = match std::fs::read_to_string(&file) {
-            Ok(ok) => ok,
+            if let Err(err) = result {
+                eprintln!("{}: {err}", display_name(path));
+            }
+
+            // standard input isn't followed, like GNU tail does with pipes
+            continue;
+        }
+
+        let mut file = match File::open(path) {
+            Ok(file) => file,
             Err(err) => {
-                eprintln!("{}: {err}", file.display());
+                eprintln!("{}: {err}", path.display());
+                followed.push(Followed {
+                    index,
+                    path: path.clone(),
+                    file: None,
+                    position: 0,
+                    partial: Vec::new(),
+                });
                 continue;
             }
         };
 
-        if display_header {
-            println!("==> {} <==", file.display());
-        }
+        headers.print(index, path);
 
-        match config.display_mode {
-            DisplayMode::Bytes => print!("{}", get_bytes(&string, config.take_value)),
-            DisplayMode::Lines => print!("{}", get_lines(&string, config.take_value)),
-        }
+        let mut output = LossyWriter {
+            inner: stdout.lock(),
+            pending: Vec::new(),
+        };
+        let result = match file.metadata() {
+            Ok(metadata) if metadata.is_file() => tail_file(&mut file, &config, &mut output),
+            Ok(_) => tail_stream(BufReader::new(&mut file), &config, &mut output).map(|_| 0),
+            Err(err) => Err(err),
+        };
+        let result = result.and_then(|position| output.finish().map(|_| position));
 
-        if display_header {
-            println!();
+        match result {
+            Ok(position) => followed.push(Followed {
+                index,
+                path: path.clone(),
+                file: Some(file),
+                position,
+                partial: Vec::new(),
+            }),
+            Err(err) => eprintln!("{}: {err}", path.display()),
         }
     }
+
+    if let Some(mode) = config.follow {
+        follow(&config, mode, followed, &mut headers);
+    }
 }
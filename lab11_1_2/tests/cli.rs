@@ -1,3 +1,6 @@
+// the tests pass their arguments as `&[..]`, which newer versions of clippy flag
+#![allow(clippy::needless_borrows_for_generic_args)]
+
 use assert_cmd::Command;
 use predicates::prelude::*;
 use rand::{distributions::Alphanumeric, Rng};
@@ -12,6 +15,8 @@ const ONE: &str = "tests/inputs/one.txt";
 const TWO: &str = "tests/inputs/two.txt";
 const THREE: &str = "tests/inputs/three.txt";
 const TEN: &str = "tests/inputs/ten.txt";
+const NUMBERS: &str = "tests/inputs/numbers.txt";
+const MANIFEST: &str = "tests/inputs/manifest.txt";
 
 //============================================================================
 fn random_string() -> String {
@@ -829,3 +834,465 @@ fn multiple_files_c_plus_3() -> TestResult {
         "tests/expected/all.c+3.out",
     )
 }
+
+//============================================================================
+fn temp_file(contents: &str) -> Result<std::path::PathBuf, Box<dyn std::error::Error>> {
+    let path = std::env::temp_dir().join(format!("tailr-{}", random_string()));
+    fs::write(&path, contents)?;
+    Ok(path)
+}
+
+//============================================================================
+/// Waits until tailr has printed `expected`, or for 10 seconds at most so that a wrong output
+/// fails the assertions of the test instead of hanging.
+fn wait_for_output(output: &std::sync::Mutex<Vec<u8>>, expected: &[u8]) {
+    use std::time::{Duration, Instant};
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while output.lock().unwrap().as_slice() != expected && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+//============================================================================
+/// Runs tailr with `--pid` of a process that lives until tailr has printed `ready`, been changed
+/// by `change` and printed `settled`.
+fn run_follow(
+    args: &[&str],
+    ready: &[u8],
+    change: impl FnOnce() -> std::io::Result<()>,
+    settled: &[u8],
+) -> Result<std::process::Output, Box<dyn std::error::Error>> {
+    use std::io::Read;
+    use std::process::{Command, Stdio};
+    use std::sync::{Arc, Mutex};
+
+    let mut sleep = Command::new("sleep").arg("60").spawn()?;
+    let mut tailr = Command::new(assert_cmd::cargo::cargo_bin(PRG))
+        .args(["-s", "0.1", "--pid", &sleep.id().to_string()])
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let stdout = Arc::new(Mutex::new(Vec::new()));
+    let reader = {
+        let stdout = stdout.clone();
+        let mut pipe = tailr.stdout.take().unwrap();
+
+        std::thread::spawn(move || {
+            let mut buffer = [0; 1024];
+            while let Ok(read @ 1..) = pipe.read(&mut buffer) {
+                stdout.lock().unwrap().extend_from_slice(&buffer[..read]);
+            }
+        })
+    };
+
+    wait_for_output(&stdout, ready);
+    change()?;
+    wait_for_output(&stdout, settled);
+
+    // tailr exits once the process is gone, not while it's a zombie
+    sleep.kill()?;
+    sleep.wait()?;
+
+    let mut output = tailr.wait_with_output()?;
+    reader.join().unwrap();
+    output.stdout = std::mem::take(&mut *stdout.lock().unwrap());
+
+    Ok(output)
+}
+
+//============================================================================
+#[test]
+fn follow_appended() -> TestResult {
+    let path = temp_file("one\ntwo\n")?;
+    let output = run_follow(
+        &["-f", "-n", "1", path.to_str().unwrap()],
+        b"two\n",
+        || {
+            let mut file = fs::OpenOptions::new().append(true).open(&path)?;
+            std::io::Write::write_all(&mut file, b"three\n")
+        },
+        b"two\nthree\n",
+    )?;
+    fs::remove_file(&path)?;
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout)?, "two\nthree\n");
+    Ok(())
+}
+
+//============================================================================
+#[test]
+fn follow_truncated() -> TestResult {
+    let path = temp_file("one\ntwo\n")?;
+    let output = run_follow(
+        &["-f", path.to_str().unwrap()],
+        b"one\ntwo\n",
+        || fs::write(&path, "3\n"),
+        b"one\ntwo\n3\n",
+    )?;
+    fs::remove_file(&path)?;
+
+    assert_eq!(String::from_utf8(output.stdout)?, "one\ntwo\n3\n");
+    assert!(String::from_utf8(output.stderr)?.contains("file truncated"));
+    Ok(())
+}
+
+//============================================================================
+#[test]
+fn follow_name_rotated() -> TestResult {
+    let path = temp_file("one\n")?;
+    let rotated = path.with_extension("1");
+    let output = run_follow(
+        &["-F", path.to_str().unwrap()],
+        b"one\n",
+        || {
+            fs::rename(&path, &rotated)?;
+            let mut file = fs::OpenOptions::new().append(true).open(&rotated)?;
+            std::io::Write::write_all(&mut file, b"two\n")?;
+            fs::write(&path, "three\n")
+        },
+        b"one\ntwo\nthree\n",
+    )?;
+    fs::remove_file(&path)?;
+    fs::remove_file(&rotated)?;
+
+    assert_eq!(String::from_utf8(output.stdout)?, "one\ntwo\nthree\n");
+    assert!(String::from_utf8(output.stderr)?.contains("has been replaced"));
+    Ok(())
+}
+
+//============================================================================
+#[test]
+fn follow_descriptor_renamed() -> TestResult {
+    let path = temp_file("one\n")?;
+    let renamed = path.with_extension("1");
+    let output = run_follow(
+        &["--follow=descriptor", path.to_str().unwrap()],
+        b"one\n",
+        || {
+            fs::rename(&path, &renamed)?;
+            let mut file = fs::OpenOptions::new().append(true).open(&renamed)?;
+            std::io::Write::write_all(&mut file, b"two\n")?;
+            fs::write(&path, "three\n")
+        },
+        b"one\ntwo\n",
+    )?;
+    fs::remove_file(&path)?;
+    fs::remove_file(&renamed)?;
+
+    assert_eq!(String::from_utf8(output.stdout)?, "one\ntwo\n");
+    Ok(())
+}
+
+//============================================================================
+#[test]
+fn follow_multiple_headers() -> TestResult {
+    let first = temp_file("one\n")?;
+    let second = temp_file("two\n")?;
+    let (first_name, second_name) = (first.to_str().unwrap(), second.to_str().unwrap());
+    let ready = format!("==> {first_name} <==\none\n\n==> {second_name} <==\ntwo\n");
+    let settled = format!("{ready}\n==> {first_name} <==\nthree\n");
+    let output = run_follow(
+        &["-f", first_name, second_name],
+        ready.as_bytes(),
+        || {
+            let mut file = fs::OpenOptions::new().append(true).open(&first)?;
+            std::io::Write::write_all(&mut file, b"three\n")
+        },
+        settled.as_bytes(),
+    )?;
+    fs::remove_file(&first)?;
+    fs::remove_file(&second)?;
+
+    assert_eq!(String::from_utf8(output.stdout)?, settled);
+    Ok(())
+}
+
+//============================================================================
+#[test]
+fn dies_bad_sleep_interval() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-f", "-s", "soon", ONE])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid number of seconds: 'soon'",
+        ));
+
+    Ok(())
+}
+
+//============================================================================
+fn run_stdin(args: &[&str], input_file: &str, expected_file: &str) -> TestResult {
+    let input = fs::read(input_file)?;
+    let expected = String::from_utf8_lossy(&fs::read(expected_file)?).to_string();
+
+    Command::cargo_bin(PRG)?
+        .args(args)
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(expected)
+        .stderr("");
+
+    Ok(())
+}
+
+//============================================================================
+#[test]
+fn stdin_lines() -> TestResult {
+    run_stdin(&["-"], TEN, "tests/expected/ten.txt.out")?;
+    run_stdin(&["-n", "3", "-"], TEN, "tests/expected/ten.txt.n3.out")?;
+    run_stdin(&["-n", "3", "-"], THREE, "tests/expected/three.txt.n3.out")?;
+    run_stdin(
+        &["-n", "+2", "-"],
+        THREE,
+        "tests/expected/three.txt.n+2.out",
+    )?;
+    run_stdin(&["-n", "0", "-"], TWO, "tests/expected/two.txt.n0.out")
+}
+
+//============================================================================
+#[test]
+fn stdin_bytes() -> TestResult {
+    run_stdin(&["-c", "3", "-"], ONE, "tests/expected/one.txt.c3.out")?;
+    run_stdin(&["-c", "200", "-"], TEN, "tests/expected/ten.txt.c200.out")?;
+    run_stdin(&["-c", "+2", "-"], TWO, "tests/expected/two.txt.c+2.out")
+}
+
+//============================================================================
+#[test]
+fn stdin_header() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-n", "1", ONE, "-"])
+        .write_stdin("a\nb\n")
+        .assert()
+        .success()
+        .stdout(
+            "==> tests/inputs/one.txt <==\nÖne line, four wordś.\n\n==> standard input <==\nb\n",
+        );
+
+    Ok(())
+}
+
+//============================================================================
+#[test]
+fn large_file_crlf() -> TestResult {
+    // lines across a lot of the blocks that are read backwards
+    let input: String = (1..=100_000).map(|x| format!("{x}\r\n")).collect();
+    let path = temp_file(&input)?;
+    let name = path.to_str().unwrap();
+
+    let last: String = (99_998..=100_000).map(|x| format!("{x}\r\n")).collect();
+    Command::cargo_bin(PRG)?
+        .args(["-n", "3", name])
+        .assert()
+        .success()
+        .stdout(last)
+        .stderr("");
+
+    let many: String = (30_001..=100_000).map(|x| format!("{x}\r\n")).collect();
+    Command::cargo_bin(PRG)?
+        .args(["-n", "70000", name])
+        .assert()
+        .success()
+        .stdout(many.clone());
+    Command::cargo_bin(PRG)?
+        .args(["-n", "70000", "-"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(many);
+
+    fs::remove_file(&path)?;
+    Ok(())
+}
+
+//============================================================================
+#[test]
+fn numbers_c1k() -> TestResult {
+    run(&[NUMBERS, "-c", "1K"], "tests/expected/numbers.txt.c1K.out")?;
+    run(&[NUMBERS, "-c", "1k"], "tests/expected/numbers.txt.c1K.out")?;
+    run(
+        &[NUMBERS, "-c", "1kiB"],
+        "tests/expected/numbers.txt.c1K.out",
+    )?;
+    run(
+        &[NUMBERS, "-c", "1024"],
+        "tests/expected/numbers.txt.c1K.out",
+    )
+}
+
+#[test]
+fn numbers_c2kb() -> TestResult {
+    run(
+        &[NUMBERS, "-c", "2kB"],
+        "tests/expected/numbers.txt.c2kB.out",
+    )
+}
+
+#[test]
+fn numbers_c1b() -> TestResult {
+    run(&[NUMBERS, "-c=-1b"], "tests/expected/numbers.txt.c1b.out")
+}
+
+#[test]
+fn numbers_c_plus_1kib() -> TestResult {
+    run(
+        &[NUMBERS, "-c", "+1KiB"],
+        "tests/expected/numbers.txt.c+1KiB.out",
+    )
+}
+
+#[test]
+fn numbers_c1mib() -> TestResult {
+    run(
+        &[NUMBERS, "-c", "1MiB"],
+        "tests/expected/numbers.txt.c1MiB.out",
+    )?;
+    run(
+        &[NUMBERS, "-c", "1m"],
+        "tests/expected/numbers.txt.c1MiB.out",
+    )?;
+    run(
+        &[NUMBERS, "-c", "1mB"],
+        "tests/expected/numbers.txt.c1MiB.out",
+    )
+}
+
+#[test]
+fn numbers_n1k() -> TestResult {
+    run(&[NUMBERS, "-n", "1K"], "tests/expected/numbers.txt.n1K.out")
+}
+
+#[test]
+fn numbers_n_plus_1k() -> TestResult {
+    run(
+        &[NUMBERS, "-n", "+1K"],
+        "tests/expected/numbers.txt.n+1K.out",
+    )
+}
+
+#[test]
+fn numbers_n1kb() -> TestResult {
+    run(
+        &[NUMBERS, "-n", "1kB"],
+        "tests/expected/numbers.txt.n1kB.out",
+    )
+}
+
+#[test]
+fn numbers_overflow_is_everything() -> TestResult {
+    run(
+        &[NUMBERS, "-n", "99999999999999999999"],
+        "tests/expected/numbers.txt.all.out",
+    )?;
+    run(&[NUMBERS, "-c", "1Q"], "tests/expected/numbers.txt.all.out")?;
+    run(
+        &[NUMBERS, "-c", "8EiB"],
+        "tests/expected/numbers.txt.all.out",
+    )?;
+    run(&[NUMBERS, "-n", "+99999999999999999999"], EMPTY)
+}
+
+#[test]
+fn dies_bad_suffix() -> TestResult {
+    for bad in ["1x", "1g", "1gB", "K", "+"] {
+        Command::cargo_bin(PRG)?
+            .args(["-c", bad, NUMBERS])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(format!(
+                "illegal byte count -- {bad}"
+            )));
+    }
+
+    Ok(())
+}
+
+//============================================================================
+#[test]
+fn manifest_zero_terminated() -> TestResult {
+    run(
+        &["-z", "-n", "3", MANIFEST],
+        "tests/expected/manifest.txt.z.n3.out",
+    )?;
+    run(
+        &["--separator", "nul", "-n", "3", MANIFEST],
+        "tests/expected/manifest.txt.z.n3.out",
+    )?;
+    run_stdin(
+        &["-z", "-n", "3", "-"],
+        MANIFEST,
+        "tests/expected/manifest.txt.z.n3.out",
+    )
+}
+
+#[test]
+fn manifest_zero_terminated_plus_2() -> TestResult {
+    run(
+        &["-z", "-n", "+2", MANIFEST],
+        "tests/expected/manifest.txt.z.n+2.out",
+    )?;
+    run_stdin(
+        &["-z", "-n", "+2", "-"],
+        MANIFEST,
+        "tests/expected/manifest.txt.z.n+2.out",
+    )
+}
+
+#[test]
+fn cr_lf_only() -> TestResult {
+    run(
+        &["--separator", "lf", "-n", "2", "tests/inputs/cr.txt"],
+        "tests/expected/cr.txt.lf.n2.out",
+    )?;
+    run(
+        &["--separator", "lf", "-n", "+3", "tests/inputs/cr.txt"],
+        EMPTY,
+    )
+}
+
+#[test]
+fn cr_universal() -> TestResult {
+    run(
+        &["-n", "2", "tests/inputs/cr.txt"],
+        "tests/expected/cr.txt.n2.out",
+    )?;
+    run(
+        &[
+            "--separator",
+            "universal",
+            "-n",
+            "+3",
+            "tests/inputs/cr.txt",
+        ],
+        "tests/expected/cr.txt.n2.out",
+    )
+}
+
+//============================================================================
+#[test]
+fn follow_zero_terminated() -> TestResult {
+    let path = temp_file("one\0two\0")?;
+    let output = run_follow(
+        &["-f", "-z", "-n", "1", path.to_str().unwrap()],
+        b"two\0",
+        || {
+            let mut file = fs::OpenOptions::new().append(true).open(&path)?;
+            std::io::Write::write_all(&mut file, b"thr")?;
+            // likely read on its own, which mustn't be printed yet
+            std::thread::sleep(std::time::Duration::from_millis(200));
+            std::io::Write::write_all(&mut file, b"ee\0fo")
+        },
+        b"two\0three\0",
+    )?;
+    fs::remove_file(&path)?;
+
+    // the records are printed whole, and what's left once the process exits
+    assert_eq!(output.stdout, b"two\0three\0fo");
+    Ok(())
+}
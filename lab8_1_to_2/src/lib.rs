@@ -1,24 +1,191 @@
 use std::{
     error::Error,
-    io::{BufRead, BufReader},
+    io::{BufRead, BufReader, BufWriter, Write},
     ops::Range,
     path::PathBuf,
 };
 
 use clap::{App, Arg};
 use csv::{ReaderBuilder, StringRecord};
+use regex::bytes::Regex;
+use unicode_segmentation::UnicodeSegmentation;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum ExtractKind {
     Fields,
     Bytes,
+
+    /// Like `Bytes` but never splits a multi-byte UTF-8 sequence, a character is extracted when
+    /// its last byte is selected.
+    BytesNoSplit,
     Chars,
+
+    /// Like `Chars` but counts extended grapheme clusters (Unicode UAX #29).
+    Graphemes,
+}
+
+/// What separates the fields of a line.
+#[derive(Debug, Clone)]
+pub enum Delimiter {
+    /// A literal separator, which may be several bytes long.
+    Literal(Vec<u8>),
+    /// Every match of the regular expression separates two fields.
+    Regex(Regex),
+}
+
+impl Delimiter {
+    fn split<'a>(&self, line: &'a [u8]) -> Vec<&'a [u8]> {
+        match self {
+            Delimiter::Literal(literal) => {
+                let mut fields = Vec::new();
+                let mut start = 0;
+                let mut index = 0;
+
+                while index + literal.len() <= line.len() {
+                    if line[index..].starts_with(literal) {
+                        fields.push(&line[start..index]);
+                        index += literal.len();
+                        start = index;
+                    } else {
+                        index += 1;
+                    }
+                }
+
+                fields.push(&line[start..]);
+                fields
+            }
+            Delimiter::Regex(regex) => regex.split(line).collect(),
+        }
+    }
+
+    fn is_found_in(&self, line: &[u8]) -> bool {
+        match self {
+            Delimiter::Literal(literal) => line.windows(literal.len()).any(|x| x == literal),
+            Delimiter::Regex(regex) => regex.is_match(line),
+        }
+    }
+}
+
+/// How lines are split into fields when extracting with `--fields`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FieldMode {
+    /// Splits every line on the delimiter, like POSIX `cut`.
+    Plain,
+    /// Parses the input as RFC 4180 CSV and quotes the output fields when needed.
+    Csv,
+}
+
+/// A range of items, zero-based and half-open; the ranges with an omitted upper bound end at
+/// `usize::MAX`. The items of a reversed range like `5-3` are taken from the last one down, which
+/// is only done while they're selected so that a long range takes no memory.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub range: Range<usize>,
+    pub reversed: bool,
+}
+
+impl From<Range<usize>> for Span {
+    fn from(range: Range<usize>) -> Self {
+        Self {
+            range,
+            reversed: false,
+        }
+    }
+}
+
+impl Span {
+    /// The items of the span that are in `items`, in the order they're taken.
+    fn select<'a, T>(&self, items: &'a [T]) -> impl Iterator<Item = &'a T> {
+        let items = &items[clamp_range(&self.range, items.len())];
+        let (forward, backward) = if self.reversed {
+            (&items[..0], items)
+        } else {
+            (items, &items[..0])
+        };
+
+        forward.iter().chain(backward.iter().rev())
+    }
+
+    /// Splits a reversed span into its items, of which there are at most `len`, so that each of
+    /// them is a part of its own. The other spans are a single part.
+    fn parts(&self, len: usize) -> Vec<Span> {
+        if !self.reversed {
+            return vec![self.clone()];
+        }
+
+        clamp_range(&self.range, len)
+            .rev()
+            .map(|x| Span::from(x..x + 1))
+            .collect()
+    }
+}
+
+/// An item of the list given to `--bytes`, `--chars` or `--fields`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Selection {
+    /// The items given as numbers or ranges of them.
+    Range(Span),
+    /// A field named in the header, which is the first record of the input.
+    Name(String),
+}
+
+/// The order in which the selected bytes, characters or fields are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Order {
+    /// In the order they appear in the line, each of them at most once, like POSIX `cut`.
+    Input,
+    /// In the order of the list, so `3,1,1` prints the third item followed by the first one twice
+    /// and `5-3` prints the items from the fifth down to the third.
+    Spec,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Extract {
     kind: ExtractKind,
-    ranges: Vec<Range<usize>>,
+    selections: Vec<Selection>,
+
+    /// Selects everything except the listed ranges.
+    complement: bool,
+    order: Order,
+}
+
+impl Extract {
+    /// Gets the ranges that should be extracted from each line, taking `complement` into account.
+    /// The field names are looked up in `header`.
+    fn selected_ranges(&self, header: &[&[u8]]) -> Result<Vec<Span>, Box<dyn Error>> {
+        let mut spans = Vec::new();
+
+        for selection in &self.selections {
+            match selection {
+                Selection::Range(span) => spans.push(span.clone()),
+                Selection::Name(name) => {
+                    let index = header
+                        .iter()
+                        .position(|x| *x == name.as_bytes())
+                        .ok_or_else(|| format!("illegal list value: \"{name}\""))?;
+
+                    spans.push(Span::from(index..index + 1));
+                }
+            }
+        }
+
+        // the direction only matters in the order of the list
+        let ranges = || spans.iter().map(|x| x.range.clone()).collect::<Vec<_>>();
+
+        if self.complement {
+            Ok(complement_ranges(&ranges())
+                .into_iter()
+                .map(Span::from)
+                .collect())
+        } else if self.order == Order::Input {
+            Ok(merge_ranges(&ranges())
+                .into_iter()
+                .map(Span::from)
+                .collect())
+        } else {
+            Ok(spans)
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -27,14 +194,29 @@ pub enum Input {
     Stdin,
 }
 
-#[derive(Debug, Clone, PartialEq, Hash)]
+#[derive(Debug, Clone)]
 pub struct Config {
     input: Input,
-    delimiter: u8,
+    delimiter: Delimiter,
     extract: Extract,
+    field_mode: FieldMode,
+    output_delimiter: Option<String>,
+    only_delimited: bool,
+}
+
+/// Parses a number of the list, which is below the `usize::MAX` that ends the open ranges.
+fn parse_list_number(number: &str) -> Result<Option<usize>, Box<dyn Error>> {
+    match number.parse::<usize>() {
+        Ok(usize::MAX) => Err(format!("list value \"{number}\" is too large").into()),
+        Ok(number) => Ok(Some(number)),
+        Err(err) if *err.kind() == std::num::IntErrorKind::PosOverflow => {
+            Err(format!("list value \"{number}\" is too large").into())
+        }
+        Err(_) => Ok(None),
+    }
 }
 
-fn parse_range(range_str: &str) -> Result<Vec<Range<usize>>, Box<dyn Error>> {
+fn parse_range(range_str: &str) -> Result<Vec<Span>, Box<dyn Error>> {
     let ranges = range_str.split(',');
     let mut result = Vec::new();
 
@@ -56,7 +238,16 @@ fn parse_range(range_str: &str) -> Result<Vec<Range<usize>>, Box<dyn Error>> {
                     }
                 })
                 .transpose()?
-                .and_then(|x| x.parse::<usize>().ok())
+                .map(|x| {
+                    // an omitted lower bound starts from the first item
+                    if x.is_empty() {
+                        Ok(Some(1))
+                    } else {
+                        parse_list_number(x)
+                    }
+                })
+                .transpose()?
+                .flatten()
                 .ok_or_else(illegal_list_value_err)?;
             let upper_bound = bounds
                 .next()
@@ -68,26 +259,44 @@ fn parse_range(range_str: &str) -> Result<Vec<Range<usize>>, Box<dyn Error>> {
                     }
                 })
                 .transpose()?
-                .and_then(|x| x.parse::<usize>().ok())
+                .map(|x| {
+                    // an omitted upper bound runs until the end of the line
+                    if x.is_empty() {
+                        Ok(Some(None))
+                    } else {
+                        parse_list_number(x).map(|x| x.map(Some))
+                    }
+                })
+                .transpose()?
+                .flatten()
                 .ok_or_else(illegal_list_value_err)?;
 
             if bounds.next().is_some() {
                 return Err(illegal_list_value_err().into());
             }
 
+            // "-" alone doesn't select anything
+            if range == "-" {
+                return Err(illegal_list_value_err().into());
+            }
+
             // lower bound and upper bound must not be zero.
             if lower_bound == 0 {
                 return Err(format!("illegal list value: \"{lower_bound}\"").into());
             }
-            if upper_bound == 0 {
-                return Err(format!("illegal list value: \"{upper_bound}\"").into());
-            }
-
-            if lower_bound > upper_bound {
-                return Err(format!("First number in range ({lower_bound}) must be lower than second number ({upper_bound})").into());
+            if upper_bound == Some(0) {
+                return Err("illegal list value: \"0\"".to_string().into());
             }
 
-            result.push(lower_bound - 1..upper_bound);
+            result.push(match upper_bound {
+                // a reversed range like "5-3" selects 5, 4 and 3 in that order
+                Some(upper_bound) if lower_bound > upper_bound => Span {
+                    range: upper_bound - 1..lower_bound,
+                    reversed: true,
+                },
+                Some(upper_bound) => Span::from(lower_bound - 1..upper_bound),
+                None => Span::from(lower_bound - 1..usize::MAX),
+            });
         } else {
             // check if range contains '+'
             if range.contains('+') {
@@ -95,22 +304,111 @@ fn parse_range(range_str: &str) -> Result<Vec<Range<usize>>, Box<dyn Error>> {
             }
 
             // parse unit range
-            let number = range
-                .parse::<usize>()
-                .map_err(|_| illegal_list_value_err())?;
+            let number = parse_list_number(range)?.ok_or_else(illegal_list_value_err)?;
 
             // the number can't be zero
             if number == 0 {
                 return Err(illegal_list_value_err().into());
             }
 
-            result.push(number - 1..number);
+            result.push(Span::from(number - 1..number));
+        }
+    }
+
+    Ok(result)
+}
+
+/// Parses the list given to `--fields`, where the items that aren't numbers or ranges are field
+/// names.
+fn parse_field_list(list_str: &str) -> Result<Vec<Selection>, Box<dyn Error>> {
+    let mut result = Vec::new();
+
+    for item in list_str.split(',') {
+        let is_range = item
+            .chars()
+            .all(|x| x.is_ascii_digit() || x == '-' || x == '+');
+
+        if is_range {
+            result.extend(parse_range(item)?.into_iter().map(Selection::Range));
+        } else {
+            result.push(Selection::Name(item.to_string()));
+        }
+    }
+
+    Ok(result)
+}
+
+/// Replaces the escape sequences `\t`, `\n`, `\r`, `\0` and `\\` with the characters they stand
+/// for.
+fn unescape(value: &str) -> Result<String, Box<dyn Error>> {
+    let mut result = String::new();
+    let mut chars = value.chars();
+
+    while let Some(char) = chars.next() {
+        if char != '\\' {
+            result.push(char);
+            continue;
         }
+
+        result.push(match chars.next() {
+            Some('t') => '\t',
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('0') => '\0',
+            Some('\\') => '\\',
+            _ => return Err(format!("invalid escape sequence in \"{value}\"").into()),
+        });
     }
 
     Ok(result)
 }
 
+/// Sorts the ranges and merges the ones that overlap or touch each other, so every item is covered
+/// at most once.
+fn merge_ranges(ranges: &[Range<usize>]) -> Vec<Range<usize>> {
+    let mut sorted = ranges.to_vec();
+    sorted.sort_by_key(|x| x.start);
+
+    let mut result: Vec<Range<usize>> = Vec::new();
+
+    for range in sorted {
+        match result.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => result.push(range),
+        }
+    }
+
+    result
+}
+
+/// Gets the ranges that aren't covered by any of the given ranges.
+fn complement_ranges(ranges: &[Range<usize>]) -> Vec<Range<usize>> {
+    let mut sorted = ranges.to_vec();
+    sorted.sort_by_key(|x| x.start);
+
+    let mut result = Vec::new();
+    let mut next = 0;
+
+    for range in sorted {
+        if range.start > next {
+            result.push(next..range.start);
+        }
+
+        next = next.max(range.end);
+    }
+
+    if next < usize::MAX {
+        result.push(next..usize::MAX);
+    }
+
+    result
+}
+
+/// Clamps the range so that it lies within `0..len`.
+fn clamp_range(range: &Range<usize>, len: usize) -> Range<usize> {
+    range.start.min(len)..range.end.min(len)
+}
+
 impl Config {
     pub fn get_from_args() -> Result<Self, Box<dyn Error>> {
         let matches = App::new("cutr")
@@ -131,6 +429,7 @@ impl Config {
                     .long("bytes")
                     .short("b")
                     .takes_value(true)
+                    .allow_hyphen_values(true)
                     .help("Selected bytes")
                     .conflicts_with("chars")
                     .conflicts_with("fields"),
@@ -141,6 +440,7 @@ impl Config {
                     .long("chars")
                     .short("c")
                     .takes_value(true)
+                    .allow_hyphen_values(true)
                     .help("Selected characters")
                     .conflicts_with("bytes")
                     .conflicts_with("fields"),
@@ -151,6 +451,7 @@ impl Config {
                     .long("fields")
                     .short("f")
                     .takes_value(true)
+                    .allow_hyphen_values(true)
                     .help("Selected fields")
                     .conflicts_with("bytes")
                     .conflicts_with("chars"),
@@ -164,8 +465,79 @@ impl Config {
                     .takes_value(true)
                     .default_value("\t"),
             )
+            .arg(
+                Arg::with_name("regex_delimiter")
+                    .value_name("REGEX")
+                    .long("regex-delim")
+                    .takes_value(true)
+                    .help("Split fields on every match of REGEX instead of --delim")
+                    .conflicts_with("delimiter")
+                    .conflicts_with("bytes")
+                    .conflicts_with("chars"),
+            )
+            .arg(
+                Arg::with_name("csv")
+                    .long("csv")
+                    .help("Parse and print fields as RFC 4180 CSV")
+                    .conflicts_with("bytes")
+                    .conflicts_with("chars")
+                    .conflicts_with("regex_delimiter"),
+            )
+            .arg(
+                Arg::with_name("graphemes")
+                    .long("graphemes")
+                    .help("Count extended grapheme clusters instead of characters with --chars")
+                    .requires("chars"),
+            )
+            .arg(
+                Arg::with_name("no_split")
+                    .short("n")
+                    .help("Do not split multi-byte characters with --bytes")
+                    .requires("bytes"),
+            )
+            .arg(
+                Arg::with_name("complement")
+                    .long("complement")
+                    .help("Select everything except the given bytes, characters or fields"),
+            )
+            .arg(
+                Arg::with_name("order")
+                    .value_name("ORDER")
+                    .long("order")
+                    .takes_value(true)
+                    .possible_values(&["input", "spec"])
+                    .default_value("spec")
+                    .help(
+                        "Print the selection in the order of the input, each at most once, or in \
+                         the order of the list (spec), which allows repeated and reversed ranges \
+                         like 3,1,1,5-3",
+                    ),
+            )
+            .arg(
+                Arg::with_name("output_delimiter")
+                    .value_name("STRING")
+                    .long("output-delimiter")
+                    .takes_value(true)
+                    .help(
+                        "Use STRING as the output delimiter, defaults to the input delimiter or a \
+                         tab with --regex-delim",
+                    ),
+            )
+            .arg(
+                Arg::with_name("only_delimited")
+                    .long("only-delimited")
+                    .short("s")
+                    .help("Do not print lines without any delimiter")
+                    .requires("fields"),
+            )
             .get_matches();
 
+        let order = match matches.value_of("order") {
+            Some("input") => Order::Input,
+            Some("spec") => Order::Spec,
+            _ => unreachable!("should've been validated by clap"),
+        };
+
         let extract = match (
             matches.value_of("bytes"),
             matches.value_of("chars"),
@@ -173,18 +545,38 @@ impl Config {
         ) {
             // bytes extract
             (Some(bytes), None, None) => Extract {
-                kind: ExtractKind::Bytes,
-                ranges: parse_range(bytes)?,
+                kind: if matches.is_present("no_split") {
+                    ExtractKind::BytesNoSplit
+                } else {
+                    ExtractKind::Bytes
+                },
+                selections: parse_range(bytes)?
+                    .into_iter()
+                    .map(Selection::Range)
+                    .collect(),
+                complement: matches.is_present("complement"),
+                order,
             },
             // chars extract
             (None, Some(chars), None) => Extract {
-                kind: ExtractKind::Chars,
-                ranges: parse_range(chars)?,
+                kind: if matches.is_present("graphemes") {
+                    ExtractKind::Graphemes
+                } else {
+                    ExtractKind::Chars
+                },
+                selections: parse_range(chars)?
+                    .into_iter()
+                    .map(Selection::Range)
+                    .collect(),
+                complement: matches.is_present("complement"),
+                order,
             },
             // fields extract
-            (None, None, Some(chars)) => Extract {
+            (None, None, Some(fields)) => Extract {
                 kind: ExtractKind::Fields,
-                ranges: parse_range(chars)?,
+                selections: parse_field_list(fields)?,
+                complement: matches.is_present("complement"),
+                order,
             },
             // no extract
             (None, None, None) => {
@@ -193,16 +585,27 @@ impl Config {
             _ => unreachable!(),
         };
 
-        let delimiter = {
-            let value = matches
-                .value_of("delimiter")
-                .expect("should've a value, atleast default");
+        let delimiter = match matches.value_of("regex_delimiter") {
+            Some(pattern) => Delimiter::Regex(
+                Regex::new(pattern).map_err(|_| format!("Invalid pattern \"{pattern}\""))?,
+            ),
+            None => {
+                let value = matches
+                    .value_of("delimiter")
+                    .expect("should've a value, atleast default");
+                let unescaped = unescape(value)?;
 
-            if value.as_bytes().len() != 1 {
-                return Err(format!("--delim \"{value}\" must be a single byte").into());
-            }
+                if unescaped.is_empty() {
+                    return Err(format!("--delim \"{value}\" must not be empty").into());
+                }
+
+                // the csv parser only supports single byte delimiters
+                if matches.is_present("csv") && unescaped.len() != 1 {
+                    return Err(format!("--delim \"{value}\" must be a single byte").into());
+                }
 
-            value.as_bytes()[0]
+                Delimiter::Literal(unescaped.into_bytes())
+            }
         };
 
         let files: Vec<PathBuf> = matches
@@ -212,165 +615,365 @@ impl Config {
             .collect();
 
         // for input with ["-"]
-        let input = if files.get(0).and_then(|x| x.to_str()) == Some("-") && files.len() == 1 {
+        let input = if files.first().and_then(|x| x.to_str()) == Some("-") && files.len() == 1 {
             Input::Stdin
         } else {
             Input::Files(files)
         };
 
+        let field_mode = if matches.is_present("csv") {
+            FieldMode::Csv
+        } else {
+            FieldMode::Plain
+        };
+
+        let output_delimiter = matches
+            .value_of("output_delimiter")
+            .map(unescape)
+            .transpose()?;
+
+        // the fields of a CSV record couldn't be told apart
+        if field_mode == FieldMode::Csv && output_delimiter.as_deref() == Some("") {
+            return Err("--output-delimiter must not be empty with --csv".into());
+        }
+
         Ok(Config {
             input,
             delimiter,
             extract,
+            field_mode,
+            output_delimiter,
+            only_delimited: matches.is_present("only_delimited"),
         })
     }
 }
 
-fn extract_chars(line: &str, char_ranges: &[Range<usize>]) -> String {
+fn extract_chars(line: &str, char_ranges: &[Span]) -> String {
     let all_chars = line.chars().collect::<Vec<_>>();
 
     let mut result = String::new();
 
     for char_range in char_ranges {
-        // if char_range is not in the range of all_chars, skip it
-        if char_range.end > all_chars.len() {
-            continue;
-        }
+        // the part of char_range that goes past the end of the line is dropped
+        result.extend(char_range.select(&all_chars));
+    }
 
-        // get the chars from all_chars
-        for i in char_range.clone() {
-            result.push(all_chars[i]);
-        }
+    result
+}
+
+fn extract_graphemes(line: &str, grapheme_ranges: &[Span]) -> String {
+    let all_graphemes = line.graphemes(true).collect::<Vec<_>>();
+
+    let mut result = String::new();
+
+    for grapheme_range in grapheme_ranges {
+        // the part of grapheme_range that goes past the end of the line is dropped
+        result.extend(grapheme_range.select(&all_graphemes).copied());
     }
 
     result
 }
 
-fn extract_bytes(line: &str, byte_ranges: &[Range<usize>]) -> String {
-    let mut bytes = Vec::new();
+fn extract_bytes_no_split(line: &str, byte_ranges: &[Span]) -> String {
+    let mut result = String::new();
 
     for byte_range in byte_ranges {
-        // if char_range is not in the range of all_chars, skip it
-        if byte_range.end > line.as_bytes().len() {
-            continue;
-        }
+        // a character is extracted only when its last byte falls in byte_range
+        let chars = line
+            .char_indices()
+            .filter(|(index, char)| byte_range.range.contains(&(index + char.len_utf8() - 1)))
+            .map(|(_, char)| char);
 
-        // get the chars from all_chars
-        for i in byte_range.clone() {
-            bytes.push(line.as_bytes()[i]);
+        if byte_range.reversed {
+            result.extend(chars.rev());
+        } else {
+            result.extend(chars);
         }
     }
 
+    result
+}
+
+fn extract_bytes(line: &str, byte_ranges: &[Span]) -> String {
+    let mut bytes = Vec::new();
+
+    for byte_range in byte_ranges {
+        // the part of byte_range that goes past the end of the line is dropped
+        bytes.extend(byte_range.select(line.as_bytes()));
+    }
+
     // iteratively transforms the byte into a utf-8 character
     String::from_utf8_lossy(&bytes).to_string()
 }
 
-fn extract_fields(record: &StringRecord, field_ranges: &[Range<usize>]) -> Vec<String> {
+fn extract_fields(record: &StringRecord, field_ranges: &[Span]) -> Vec<String> {
+    let fields = record.iter().collect::<Vec<_>>();
     let mut result = Vec::new();
 
     for field_range in field_ranges {
-        // if field_range is not in the range of record, skip it
-        if field_range.end > record.len() {
-            continue;
+        // the part of field_range that goes past the last field is dropped
+        result.extend(field_range.select(&fields).map(|x| x.to_string()));
+    }
+
+    result
+}
+
+fn extract_plain_fields<'a>(
+    line: &'a [u8],
+    delimiter: &Delimiter,
+    field_ranges: &[Span],
+) -> Vec<&'a [u8]> {
+    let fields = delimiter.split(line);
+
+    let mut result = Vec::new();
+
+    for field_range in field_ranges {
+        // the part of field_range that goes past the last field is dropped
+        result.extend(field_range.select(&fields).copied());
+    }
+
+    result
+}
+
+/// Writes the fields as a single RFC 4180 record, quoting only the fields that need it.
+fn write_csv_record(
+    output: &mut impl Write,
+    fields: &[String],
+    delimiter: &[u8],
+) -> std::io::Result<()> {
+    for (index, field) in fields.iter().enumerate() {
+        if index != 0 {
+            output.write_all(delimiter)?;
         }
 
-        // get the fields from record
+        let needs_quotes = field
+            .as_bytes()
+            .windows(delimiter.len())
+            .any(|x| x == delimiter)
+            || field.contains(['"', '\r', '\n']);
 
-        for i in field_range.clone() {
-            result.push(record[i].to_string());
+        if needs_quotes {
+            write!(output, "\"{}\"", field.replace('"', "\"\""))?;
+        } else {
+            output.write_all(field.as_bytes())?;
         }
     }
 
-    result
+    output.write_all(b"\n")
 }
 
-pub fn run(config: Config) {
-    let mut readers = Vec::new();
-    match config.input {
-        Input::Files(files) => {
-            for file in files {
-                match std::fs::File::open(&file) {
-                    Ok(file) => readers.push(Box::new(BufReader::new(file)) as Box<dyn BufRead>),
-                    Err(error) => eprintln!("{}: {error}", file.display()),
-                }
+/// Gets the delimiter that's placed between the extracted fields.
+fn output_delimiter(config: &Config) -> &[u8] {
+    match (&config.output_delimiter, &config.delimiter) {
+        (Some(output_delimiter), _) => output_delimiter.as_bytes(),
+        (None, Delimiter::Literal(literal)) => literal,
+        (None, Delimiter::Regex(_)) => b"\t",
+    }
+}
+
+fn cut_csv(
+    reader: impl BufRead,
+    config: &Config,
+    output: &mut impl Write,
+) -> Result<(), Box<dyn Error>> {
+    let delimiter = match &config.delimiter {
+        Delimiter::Literal(literal) if literal.len() == 1 => literal[0],
+        _ => unreachable!("--csv should've a single byte delimiter"),
+    };
+
+    // the header row is an ordinary record for `cut`, so it must not be skipped
+    let mut reader = ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(reader);
+    let mut record = StringRecord::new();
+    let mut ranges = None;
+
+    loop {
+        match reader.read_record(&mut record) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(err) => {
+                eprintln!("{err}");
+                continue;
+            }
+        }
+
+        // the field names are looked up in the first record
+        let ranges = match &ranges {
+            Some(ranges) => ranges,
+            None => {
+                let header = record.iter().map(str::as_bytes).collect::<Vec<_>>();
+                ranges.insert(config.extract.selected_ranges(&header)?)
+            }
+        };
+
+        // a record with a single field didn't contain any delimiter
+        if config.only_delimited && record.len() < 2 {
+            continue;
+        }
+
+        let fields = extract_fields(&record, ranges);
+
+        write_csv_record(output, &fields, output_delimiter(config))?;
+    }
+
+    Ok(())
+}
+
+fn cut_plain(
+    mut reader: impl BufRead,
+    config: &Config,
+    output: &mut impl Write,
+) -> Result<(), Box<dyn Error>> {
+    let mut line = Vec::new();
+    let mut ranges = None;
+
+    loop {
+        line.clear();
+
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+
+        // strip the line terminator the same way `BufRead::lines` does
+        if line.last() == Some(&b'\n') {
+            line.pop();
+
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+        }
+
+        // the field names are looked up in the first line
+        let ranges = match &ranges {
+            Some(ranges) => ranges,
+            None => ranges.insert(
+                config
+                    .extract
+                    .selected_ranges(&config.delimiter.split(&line))?,
+            ),
+        };
+
+        // lines without any delimiter are printed as is, unless `--only-delimited` is given
+        if !config.delimiter.is_found_in(&line) {
+            if !config.only_delimited {
+                output.write_all(&line)?;
+                output.write_all(b"\n")?;
             }
+            continue;
         }
-        Input::Stdin => {
-            readers.push(Box::new(BufReader::new(std::io::stdin())) as Box<dyn BufRead>)
+
+        let fields = extract_plain_fields(&line, &config.delimiter, ranges);
+
+        for (index, field) in fields.iter().enumerate() {
+            if index != 0 {
+                output.write_all(output_delimiter(config))?;
+            }
+            output.write_all(field)?;
         }
+        output.write_all(b"\n")?;
     }
 
-    let delimiter_str = {
-        let mut string = String::new();
-        string.push(config.delimiter as char);
-        string
+    Ok(())
+}
+
+fn cut_lines(
+    reader: impl BufRead,
+    config: &Config,
+    output: &mut impl Write,
+) -> Result<(), Box<dyn Error>> {
+    let extract = match config.extract.kind {
+        ExtractKind::Bytes => extract_bytes,
+        ExtractKind::BytesNoSplit => extract_bytes_no_split,
+        ExtractKind::Chars => extract_chars,
+        ExtractKind::Graphemes => extract_graphemes,
+        ExtractKind::Fields => unreachable!(),
     };
 
-    for reader in readers {
-        match config.extract.kind {
-            // use csv crate to parse delimited file
-            ExtractKind::Fields => {
-                let mut reader = ReaderBuilder::new()
-                    .delimiter(config.delimiter)
-                    .from_reader(reader);
-
-                let headers = match reader.headers() {
-                    Ok(headers) => headers,
-                    Err(err) => {
-                        eprintln!("{err}");
-                        continue;
-                    }
-                };
-
-                println!(
-                    "{}",
-                    extract_fields(headers, &config.extract.ranges).join(&delimiter_str)
-                );
-
-                let records = reader.records();
-
-                for record in records {
-                    let record = match record {
-                        Ok(record) => record,
-                        Err(err) => {
-                            eprintln!("{err}");
-                            continue;
-                        }
-                    };
-
-                    println!(
-                        "{}",
-                        extract_fields(&record, &config.extract.ranges).join(&delimiter_str)
-                    );
-                }
+    // only fields can be named, so there's no header to look up
+    let ranges = config.extract.selected_ranges(&[])?;
+
+    for line in reader.lines() {
+        let line = match line {
+            Ok(ok) => ok,
+            Err(err) => {
+                eprintln!("{err}");
+                continue;
             }
+        };
+
+        let extracted = match &config.output_delimiter {
+            // the output delimiter goes between the parts extracted by each range
+            // and between the items of the reversed ranges, of which there are at most as many as
+            // bytes
+            Some(output_delimiter) => ranges
+                .iter()
+                .flat_map(|range| range.parts(line.len()))
+                .map(|range| extract(&line, std::slice::from_ref(&range)))
+                .filter(|x| !x.is_empty())
+                .collect::<Vec<_>>()
+                .join(output_delimiter),
+            None => extract(&line, &ranges),
+        };
+
+        writeln!(output, "{extracted}")?;
+    }
+
+    Ok(())
+}
 
-            ExtractKind::Bytes | ExtractKind::Chars => {
-                for line in reader.lines() {
-                    let line = match line {
-                        Ok(ok) => ok,
-                        Err(err) => {
-                            eprintln!("{err}");
-                            continue;
-                        }
-                    };
-
-                    let output = match config.extract.kind {
-                        ExtractKind::Bytes => extract_bytes(&line, &config.extract.ranges),
-                        ExtractKind::Chars => extract_chars(&line, &config.extract.ranges),
-                        _ => unreachable!(),
-                    };
-
-                    println!("{output}")
+fn cut(
+    reader: impl BufRead,
+    config: &Config,
+    output: &mut impl Write,
+) -> Result<(), Box<dyn Error>> {
+    match (config.extract.kind, config.field_mode) {
+        (ExtractKind::Fields, FieldMode::Csv) => cut_csv(reader, config, output),
+        (ExtractKind::Fields, FieldMode::Plain) => cut_plain(reader, config, output),
+        _ => cut_lines(reader, config, output),
+    }
+}
+
+pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
+    let stdout = std::io::stdout();
+    let mut output = BufWriter::new(stdout.lock());
+
+    match &config.input {
+        Input::Files(files) => {
+            for file in files {
+                // each file is opened only when its turn comes, so the output streams
+                match std::fs::File::open(file) {
+                    Ok(opened) => cut(BufReader::new(opened), &config, &mut output)?,
+                    Err(error) => {
+                        output.flush()?;
+                        eprintln!("{}: {error}", file.display());
+                    }
                 }
             }
         }
+        Input::Stdin => cut(std::io::stdin().lock(), &config, &mut output)?,
     }
+
+    output.flush()?;
+
+    Ok(())
 }
 
 #[cfg(test)]
+#[allow(clippy::single_range_in_vec_init)]
 mod tests {
     use csv::StringRecord;
+    use regex::bytes::Regex;
+
+    use std::ops::Range;
+
+    use super::{Delimiter, Selection, Span};
+
+    fn spans(ranges: &[Range<usize>]) -> Vec<Span> {
+        ranges.iter().cloned().map(Span::from).collect()
+    }
 
     #[test]
     fn test_parse_range() {
@@ -394,72 +997,339 @@ mod tests {
 
         let res = super::parse_range("1");
         assert!(res.is_ok());
-        assert_eq!(res.unwrap(), vec![0..1]);
+        assert_eq!(res.unwrap(), spans(&[0..1]));
         let res = super::parse_range("01");
         assert!(res.is_ok());
-        assert_eq!(res.unwrap(), vec![0..1]);
+        assert_eq!(res.unwrap(), spans(&[0..1]));
         let res = super::parse_range("1,3");
         assert!(res.is_ok());
-        assert_eq!(res.unwrap(), vec![0..1, 2..3]);
+        assert_eq!(res.unwrap(), spans(&[0..1, 2..3]));
         let res = super::parse_range("001,0003");
         assert!(res.is_ok());
-        assert_eq!(res.unwrap(), vec![0..1, 2..3]);
+        assert_eq!(res.unwrap(), spans(&[0..1, 2..3]));
         let res = super::parse_range("1-3");
         assert!(res.is_ok());
-        assert_eq!(res.unwrap(), vec![0..3]);
+        assert_eq!(res.unwrap(), spans(&[0..3]));
         let res = super::parse_range("0001-03");
         assert!(res.is_ok());
-        assert_eq!(res.unwrap(), vec![0..3]);
+        assert_eq!(res.unwrap(), spans(&[0..3]));
         let res = super::parse_range("1,7,3-5");
         assert!(res.is_ok());
-        assert_eq!(res.unwrap(), vec![0..1, 6..7, 2..5]);
+        assert_eq!(res.unwrap(), spans(&[0..1, 6..7, 2..5]));
         let res = super::parse_range("15,19-20");
         assert!(res.is_ok());
-        assert_eq!(res.unwrap(), vec![14..15, 18..20]);
+        assert_eq!(res.unwrap(), spans(&[14..15, 18..20]));
+
+        // Omitted bounds are open-ended
+        let res = super::parse_range("-3");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), spans(&[0..3]));
+        let res = super::parse_range("5-");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), spans(&[4..usize::MAX]));
+        let res = super::parse_range("-2,4-");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), spans(&[0..2, 3..usize::MAX]));
+        let res = super::parse_range("-");
+        assert!(res.is_err());
+        assert_eq!(res.unwrap_err().to_string(), "illegal list value: \"-\"");
+        // Reversed ranges are kept whole, to be taken in reverse order
+        let reversed = Span {
+            range: 2..5,
+            reversed: true,
+        };
+        let res = super::parse_range("5-3");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), vec![reversed.clone()]);
+        let res = super::parse_range("3,1,1,5-3");
+        assert!(res.is_ok());
+        let mut expected = spans(&[2..3, 0..1, 0..1]);
+        expected.push(reversed);
+        assert_eq!(res.unwrap(), expected);
+        let res = super::parse_range("4000000000-1");
+        assert!(res.is_ok());
+        assert_eq!(
+            res.unwrap(),
+            vec![Span {
+                range: 0..4000000000,
+                reversed: true
+            }]
+        );
+        // Numbers that can't be told apart from an omitted upper bound are too large
+        let res = super::parse_range("18446744073709551615");
+        assert!(res.is_err());
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "list value \"18446744073709551615\" is too large"
+        );
+        let res = super::parse_range("1-99999999999999999999");
+        assert!(res.is_err());
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "list value \"99999999999999999999\" is too large"
+        );
+        let res = super::parse_range("-0");
+        assert!(res.is_err());
+        assert_eq!(res.unwrap_err().to_string(), "illegal list value: \"0\"");
+    }
+
+    #[test]
+    fn test_merge_ranges() {
+        assert_eq!(super::merge_ranges(&[2..3, 0..1, 0..1]), vec![0..1, 2..3]);
+        assert_eq!(super::merge_ranges(&[4..5, 3..4, 2..3]), vec![2..5]);
+        assert_eq!(
+            super::merge_ranges(&[5..usize::MAX, 0..2, 1..3]),
+            vec![0..3, 5..usize::MAX]
+        );
+    }
+
+    #[test]
+    fn test_complement_ranges() {
+        assert_eq!(super::complement_ranges(&[0..1]), vec![1..usize::MAX]);
+        assert_eq!(
+            super::complement_ranges(&[2..3, 0..1]),
+            vec![1..2, 3..usize::MAX]
+        );
+        assert_eq!(
+            super::complement_ranges(&[1..4, 2..3, 6..7]),
+            vec![0..1, 4..6, 7..usize::MAX]
+        );
+        assert_eq!(super::complement_ranges(&[1..usize::MAX]), vec![0..1]);
     }
 
     // tecniquely, the character á is not considered as a single utf-8 character. There seems to be
     // a character 'a' ascii and some utf-8 character that combines with the previous character to
     // form a new character. So, the character á is considered as two characters.
+    // `--graphemes` (`extract_graphemes`) counts it as a single one.
 
     #[test]
     fn test_extract_chars() {
-        assert_eq!(super::extract_chars("", &[0..1]), "".to_string());
-        assert_eq!(super::extract_chars("Ébc", &[0..1]), "É".to_string());
-        assert_eq!(super::extract_chars("Ébc", &[0..1, 2..3]), "Éc".to_string());
-        assert_eq!(super::extract_chars("Ébc", &[0..3]), "Ébc".to_string());
-        assert_eq!(super::extract_chars("Ébc", &[2..3, 1..2]), "cb".to_string());
+        assert_eq!(super::extract_chars("", &spans(&[0..1])), "".to_string());
+        assert_eq!(
+            super::extract_chars("Ébc", &spans(&[0..1])),
+            "É".to_string()
+        );
+        assert_eq!(
+            super::extract_chars("Ébc", &spans(&[0..1, 2..3])),
+            "Éc".to_string()
+        );
         assert_eq!(
-            super::extract_chars("Ébc", &[0..1, 1..2, 4..5]),
+            super::extract_chars("Ébc", &spans(&[0..3])),
+            "Ébc".to_string()
+        );
+        assert_eq!(
+            super::extract_chars("Ébc", &spans(&[2..3, 1..2])),
+            "cb".to_string()
+        );
+        assert_eq!(
+            super::extract_chars("Ébc", &spans(&[0..1, 1..2, 4..5])),
+            "Éb".to_string()
+        );
+        assert_eq!(
+            super::extract_chars("Ébc", &spans(&[1..5])),
+            "bc".to_string()
+        );
+        assert_eq!(
+            super::extract_chars("Ébc", &spans(&[1..usize::MAX])),
+            "bc".to_string()
+        );
+    }
+
+    #[test]
+    fn test_extract_graphemes() {
+        assert_eq!(
+            super::extract_graphemes("", &spans(&[0..1])),
+            "".to_string()
+        );
+        assert_eq!(
+            super::extract_graphemes("a\u{301}bc", &spans(&[0..1])),
+            "a\u{301}".to_string()
+        );
+        assert_eq!(
+            super::extract_graphemes("a\u{301}bc", &spans(&[1..3])),
+            "bc".to_string()
+        );
+        assert_eq!(
+            super::extract_graphemes("किताब", &spans(&[0..1])),
+            "कि".to_string()
+        );
+        assert_eq!(
+            super::extract_graphemes("किताब", &spans(&[2..5])),
+            "ब".to_string()
+        );
+        assert_eq!(
+            super::extract_graphemes("กำแพง", &spans(&[0..2])),
+            "กำแ".to_string()
+        );
+    }
+
+    #[test]
+    fn test_extract_bytes_no_split() {
+        assert_eq!(
+            super::extract_bytes_no_split("Ébc", &spans(&[0..1])),
+            "".to_string()
+        );
+        assert_eq!(
+            super::extract_bytes_no_split("Ébc", &spans(&[1..2])),
+            "É".to_string()
+        );
+        assert_eq!(
+            super::extract_bytes_no_split("Ébc", &spans(&[0..3])),
             "Éb".to_string()
         );
+        assert_eq!(
+            super::extract_bytes_no_split("Ébc", &spans(&[3..4, 0..2])),
+            "cÉ".to_string()
+        );
+        assert_eq!(
+            super::extract_bytes_no_split("Ébc", &spans(&[2..usize::MAX])),
+            "bc".to_string()
+        );
     }
 
     #[test]
     fn test_extract_bytes() {
-        assert_eq!(super::extract_bytes("Ébc", &[0..1]), "�".to_string());
-        assert_eq!(super::extract_bytes("Ébc", &[0..2]), "É".to_string());
-        assert_eq!(super::extract_bytes("Ébc", &[0..3]), "Éb".to_string());
-        assert_eq!(super::extract_bytes("Ébc", &[0..4]), "Ébc".to_string());
-        assert_eq!(super::extract_bytes("Ébc", &[3..4, 2..3]), "cb".to_string());
-        assert_eq!(super::extract_bytes("Ébc", &[0..2, 5..6]), "É".to_string());
+        assert_eq!(
+            super::extract_bytes("Ébc", &spans(&[0..1])),
+            "�".to_string()
+        );
+        assert_eq!(
+            super::extract_bytes("Ébc", &spans(&[0..2])),
+            "É".to_string()
+        );
+        assert_eq!(
+            super::extract_bytes("Ébc", &spans(&[0..3])),
+            "Éb".to_string()
+        );
+        assert_eq!(
+            super::extract_bytes("Ébc", &spans(&[0..4])),
+            "Ébc".to_string()
+        );
+        assert_eq!(
+            super::extract_bytes("Ébc", &spans(&[3..4, 2..3])),
+            "cb".to_string()
+        );
+        assert_eq!(
+            super::extract_bytes("Ébc", &spans(&[0..2, 5..6])),
+            "É".to_string()
+        );
+        assert_eq!(
+            super::extract_bytes("Ébc", &spans(&[2..6])),
+            "bc".to_string()
+        );
     }
 
     #[test]
     fn test_extract_fields() {
         let rec = StringRecord::from(vec!["Captain", "Sham", "12345"]);
 
-        assert_eq!(super::extract_fields(&rec, &[0..1]), &["Captain"]);
-        assert_eq!(super::extract_fields(&rec, &[1..2]), &["Sham"]);
+        assert_eq!(super::extract_fields(&rec, &spans(&[0..1])), &["Captain"]);
+        assert_eq!(super::extract_fields(&rec, &spans(&[1..2])), &["Sham"]);
         assert_eq!(
-            super::extract_fields(&rec, &[0..1, 2..3]),
+            super::extract_fields(&rec, &spans(&[0..1, 2..3])),
             &["Captain", "12345"]
         );
-        assert_eq!(super::extract_fields(&rec, &[0..1, 3..4]), &["Captain"]);
         assert_eq!(
-            super::extract_fields(&rec, &[1..2, 0..1]),
+            super::extract_fields(&rec, &spans(&[0..1, 3..4])),
+            &["Captain"]
+        );
+        assert_eq!(
+            super::extract_fields(&rec, &spans(&[1..usize::MAX])),
+            &["Sham", "12345"]
+        );
+        assert_eq!(
+            super::extract_fields(&rec, &spans(&[1..2, 0..1])),
             &["Sham", "Captain"]
         );
     }
-}
 
+    #[test]
+    fn test_extract_plain_fields() {
+        let line = "Captain\tSham\t12345".as_bytes();
+        let tab = Delimiter::Literal(b"\t".to_vec());
+        let comma = Delimiter::Literal(b",".to_vec());
+
+        assert_eq!(
+            super::extract_plain_fields(line, &tab, &spans(&[0..1])),
+            &[b"Captain"]
+        );
+        assert_eq!(
+            super::extract_plain_fields(line, &tab, &spans(&[0..1, 2..3])),
+            &[&b"Captain"[..], b"12345"]
+        );
+        assert_eq!(
+            super::extract_plain_fields(line, &tab, &spans(&[1..2, 0..1])),
+            &[&b"Sham"[..], b"Captain"]
+        );
+        assert_eq!(
+            super::extract_plain_fields(line, &tab, &spans(&[0..1, 3..4])),
+            &[b"Captain"]
+        );
+        assert_eq!(
+            super::extract_plain_fields(b"\"a,b\",c", &comma, &spans(&[0..1])),
+            &[b"\"a"]
+        );
+
+        // multi-byte and regular expression delimiters
+        let arrow = Delimiter::Literal("→".as_bytes().to_vec());
+        assert_eq!(
+            super::extract_plain_fields("a→b→c".as_bytes(), &arrow, &spans(&[1..usize::MAX])),
+            &[b"b", b"c"]
+        );
+        let spaces = Delimiter::Regex(Regex::new(" +").unwrap());
+        assert_eq!(
+            super::extract_plain_fields(b"a  b   c", &spaces, &spans(&[0..1, 2..3])),
+            &[b"a", b"c"]
+        );
+    }
+
+    #[test]
+    fn test_parse_field_list() {
+        let res = super::parse_field_list("1,3-4");
+        assert!(res.is_ok());
+        assert_eq!(
+            res.unwrap(),
+            vec![
+                Selection::Range((0..1).into()),
+                Selection::Range((2..4).into())
+            ]
+        );
+        let res = super::parse_field_list("title,2-");
+        assert!(res.is_ok());
+        assert_eq!(
+            res.unwrap(),
+            vec![
+                Selection::Name("title".to_string()),
+                Selection::Range((1..usize::MAX).into())
+            ]
+        );
+        let res = super::parse_field_list("title,0");
+        assert!(res.is_err());
+        assert_eq!(res.unwrap_err().to_string(), "illegal list value: \"0\"");
+    }
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(super::unescape("\\t").unwrap(), "\t");
+        assert_eq!(super::unescape("a\\\\b").unwrap(), "a\\b");
+        assert_eq!(super::unescape("→").unwrap(), "→");
+        assert_eq!(super::unescape("\\0\\r\\n").unwrap(), "\0\r\n");
+        assert!(super::unescape("\\q").is_err());
+        assert!(super::unescape("\\").is_err());
+    }
+
+    #[test]
+    fn test_write_csv_record() {
+        let write = |fields: &[&str]| {
+            let fields = fields.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+            let mut output = Vec::new();
+            super::write_csv_record(&mut output, &fields, b",").unwrap();
+            String::from_utf8(output).unwrap()
+        };
+
+        assert_eq!(write(&["a", "b"]), "a,b\n");
+        assert_eq!(write(&["a,b", "c"]), "\"a,b\",c\n");
+        assert_eq!(write(&["say \"hi\""]), "\"say \"\"hi\"\"\"\n");
+        assert_eq!(write(&[]), "\n");
+    }
+}
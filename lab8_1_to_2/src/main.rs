@@ -9,7 +9,10 @@ fn main() -> ExitCode {
         }
     };
 
-    cutr::run(config);
+    if let Err(err) = cutr::run(config) {
+        eprintln!("{err}");
+        return ExitCode::FAILURE;
+    }
 
     ExitCode::SUCCESS
 }
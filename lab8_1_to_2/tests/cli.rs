@@ -1,3 +1,6 @@
+// the tests pass their arguments as `&[..]`, which newer versions of clippy flag
+#![allow(clippy::needless_borrows_for_generic_args)]
+
 use assert_cmd::Command;
 use predicates::prelude::*;
 use rand::{distributions::Alphanumeric, Rng};
@@ -9,6 +12,9 @@ const PRG: &str = "cutr";
 const CSV: &str = "tests/inputs/movies1.csv";
 const TSV: &str = "tests/inputs/movies1.tsv";
 const BOOKS: &str = "tests/inputs/books.tsv";
+const BOOKS_CSV: &str = "tests/inputs/books.csv";
+const GRAPHEMES: &str = "tests/inputs/graphemes.txt";
+const BOOKS_TXT: &str = "tests/inputs/books.txt";
 
 //============================================================================
 fn random_string() -> String {
@@ -93,7 +99,16 @@ fn dies_bad_digit_chars() -> TestResult {
 fn dies_empty_delimiter() -> TestResult {
     dies(
         &[CSV, "-f", "1", "-d", ""],
-        "--delim \"\" must be a single byte",
+        "--delim \"\" must not be empty",
+    )
+}
+
+//============================================================================
+#[test]
+fn dies_empty_csv_output_delimiter() -> TestResult {
+    dies(
+        &[CSV, "-f", "1", "--csv", "--output-delimiter", ""],
+        "--output-delimiter must not be empty with --csv",
     )
 }
 
@@ -101,7 +116,7 @@ fn dies_empty_delimiter() -> TestResult {
 #[test]
 fn dies_bad_delimiter() -> TestResult {
     dies(
-        &[CSV, "-f", "1", "-d", ",,"],
+        &[CSV, "-f", "1", "-d", ",,", "--csv"],
         "--delim \",,\" must be a single byte",
     )
 }
@@ -337,3 +352,264 @@ fn tsv_c1_8() -> TestResult {
 fn repeated_value() -> TestResult {
     run(&[BOOKS, "-c", "1,1"], "tests/expected/books.c1,1.out")
 }
+
+//============================================================================
+#[test]
+fn plain_splits_quoted_field() -> TestResult {
+    run(
+        &[BOOKS_CSV, "-f", "3", "-d", ","],
+        "tests/expected/books.csv.f3.dcomma.out",
+    )
+}
+
+//============================================================================
+#[test]
+fn csv_keeps_quoted_field() -> TestResult {
+    run(
+        &[BOOKS_CSV, "-f", "3", "-d", ",", "--csv"],
+        "tests/expected/books.csv.f3.dcomma.csv.out",
+    )
+}
+
+//============================================================================
+#[test]
+fn csv_requotes_fields() -> TestResult {
+    run(
+        &[BOOKS_CSV, "-f", "1,3", "-d", ",", "--csv"],
+        "tests/expected/books.csv.f1,3.dcomma.csv.out",
+    )
+}
+
+//============================================================================
+#[test]
+fn plain_prints_line_without_delimiter() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-f", "2"])
+        .write_stdin("no delimiter\na\tb\n")
+        .assert()
+        .success()
+        .stdout("no delimiter\nb\n");
+    Ok(())
+}
+
+//============================================================================
+#[test]
+fn dies_csv_bytes() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([CSV, "-b", "1", "--csv"])
+        .assert()
+        .failure();
+    Ok(())
+}
+
+//============================================================================
+#[test]
+fn tsv_f2_open() -> TestResult {
+    run(&[TSV, "-f", "2-"], "tests/expected/movies1.tsv.f2-.out")
+}
+
+//============================================================================
+#[test]
+fn tsv_c_open_3() -> TestResult {
+    run(&[TSV, "-c", "-3"], "tests/expected/movies1.tsv.c-3.out")
+}
+
+//============================================================================
+#[test]
+fn tsv_c1_20_clamped() -> TestResult {
+    run(&[TSV, "-c", "1-20"], "tests/expected/movies1.tsv.c1-20.out")
+}
+
+//============================================================================
+#[test]
+fn tsv_f2_complement() -> TestResult {
+    run(
+        &[TSV, "-f", "2", "--complement"],
+        "tests/expected/movies1.tsv.f2.complement.out",
+    )
+}
+
+//============================================================================
+#[test]
+fn tsv_f1_3_output_delimiter() -> TestResult {
+    run(
+        &[TSV, "-f", "1,3", "--output-delimiter", " | "],
+        "tests/expected/movies1.tsv.f1,3.odelim.out",
+    )
+}
+
+//============================================================================
+#[test]
+fn only_delimited() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-f", "1", "-s"])
+        .write_stdin("no delimiter\na\tb\n")
+        .assert()
+        .success()
+        .stdout("a\n");
+    Ok(())
+}
+
+//============================================================================
+#[test]
+fn dies_only_delimited_chars() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([CSV, "-c", "1", "-s"])
+        .assert()
+        .failure();
+    Ok(())
+}
+
+//============================================================================
+#[test]
+fn graphemes_c1_2() -> TestResult {
+    run(
+        &[GRAPHEMES, "-c", "1-2", "--graphemes"],
+        "tests/expected/graphemes.txt.c1-2.graphemes.out",
+    )
+}
+
+//============================================================================
+#[test]
+fn books_b2_3_no_split() -> TestResult {
+    run(
+        &[BOOKS, "-b", "2-3", "-n"],
+        "tests/expected/books.b2-3.n.out",
+    )
+}
+
+//============================================================================
+#[test]
+fn dies_graphemes_bytes() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([CSV, "-b", "1", "--graphemes"])
+        .assert()
+        .failure();
+    Ok(())
+}
+
+//============================================================================
+#[test]
+fn dies_bad_escape_delimiter() -> TestResult {
+    dies(
+        &[CSV, "-f", "1", "-d", "\\q"],
+        "invalid escape sequence in \"\\q\"",
+    )
+}
+
+//============================================================================
+#[test]
+fn dies_unknown_field_name() -> TestResult {
+    dies(
+        &[CSV, "-f", "rating", "-d", ","],
+        "illegal list value: \"rating\"",
+    )
+}
+
+//============================================================================
+#[test]
+fn csv_f_names() -> TestResult {
+    run(
+        &[CSV, "-f", "title,year", "-d", ","],
+        "tests/expected/movies1.csv.f1-2.dcomma.out",
+    )
+}
+
+//============================================================================
+#[test]
+fn tsv_f_names_escaped_delimiter() -> TestResult {
+    run(
+        &[TSV, "-f", "year,title", "-d", "\\t"],
+        "tests/expected/movies1.tsv.fyear,title.out",
+    )
+}
+
+//============================================================================
+#[test]
+fn txt_regex_delimiter() -> TestResult {
+    run(
+        &[BOOKS_TXT, "-f", "1", "--regex-delim", " {2,}"],
+        "tests/expected/books.txt.f1.regex.out",
+    )
+}
+
+//============================================================================
+#[test]
+fn multi_byte_delimiter() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-f", "3,1", "-d", "→"])
+        .write_stdin("a→b→c\n")
+        .assert()
+        .success()
+        .stdout("c→a\n");
+    Ok(())
+}
+
+//============================================================================
+#[test]
+fn books_spec_order() -> TestResult {
+    run(
+        &[BOOKS, "-f", "3,1,1,3-2"],
+        "tests/expected/books.f3,1,1,3-2.out",
+    )
+}
+
+//============================================================================
+#[test]
+fn books_input_order() -> TestResult {
+    run(
+        &[BOOKS, "-f", "3,1,1,3-2", "--order", "input"],
+        "tests/expected/books.f3,1,1,3-2.input.out",
+    )
+}
+
+//============================================================================
+#[test]
+fn csv_c3_1_reversed() -> TestResult {
+    run(&[CSV, "-c", "3-1"], "tests/expected/movies1.csv.c3-1.out")
+}
+
+//============================================================================
+#[test]
+fn csv_b8_1_2_input_order() -> TestResult {
+    run(
+        &[CSV, "-b", "8,1-2", "--order=input"],
+        "tests/expected/movies1.csv.b8,1-2.input.out",
+    )
+}
+
+//============================================================================
+#[test]
+fn csv_f3_2_2_spec_order() -> TestResult {
+    run(
+        &[CSV, "-f", "3,2,2", "-d", ",", "--order", "spec"],
+        "tests/expected/movies1.csv.f3,2,2.dcomma.out",
+    )
+}
+
+//============================================================================
+#[test]
+fn dies_bad_order() -> TestResult {
+    dies(
+        &[CSV, "-f", "1", "--order", "random"],
+        "isn't a valid value",
+    )
+}
+
+//============================================================================
+#[test]
+fn csv_f_long_reversed_range() -> TestResult {
+    run(
+        &[CSV, "-f", "4000000000-1", "-d", ","],
+        "tests/expected/movies1.csv.f4000000000-1.dcomma.out",
+    )
+}
+
+//============================================================================
+#[test]
+fn dies_too_large_list_value() -> TestResult {
+    dies(
+        &[CSV, "-f", "1-18446744073709551615"],
+        r#"list value "18446744073709551615" is too large"#,
+    )
+}
@@ -1,11 +1,13 @@
 use std::{
     error::Error,
+    fmt::Display,
     io::{Read, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
-use clap::{App, Arg};
+use clap::{App, Arg, ArgMatches, SubCommand};
 use csv::{ReaderBuilder, Writer};
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct Point {
@@ -35,15 +37,162 @@ impl Point {
 const GREEN_TONE: &str = "#80FF8080";
 const RED_TONE: &str = "#FF808080";
 
-pub fn tag_points(pt_list: &[Point]) -> Vec<Point> {
+/// A region of the plane that a rule matches, where the boundaries are inside.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum Shape {
+    Circle {
+        center: [f64; 2],
+        radius: f64,
+    },
+    Rectangle {
+        min: [f64; 2],
+        max: [f64; 2],
+    },
+    /// The vertices in order, the edges between them are closed back to the first one.
+    Polygon {
+        vertices: Vec<[f64; 2]>,
+    },
+    /// Where `normal · (x, y) <= offset`.
+    HalfPlane {
+        normal: [f64; 2],
+        offset: f64,
+    },
+    All(Vec<Shape>),
+    Any(Vec<Shape>),
+    Not(Box<Shape>),
+}
+
+impl Shape {
+    pub fn contains(&self, pt: &Point) -> bool {
+        match self {
+            Shape::Circle { center, radius } => {
+                (pt.x - center[0]).hypot(pt.y - center[1]) <= *radius
+            }
+            Shape::Rectangle { min, max } => {
+                (min[0]..=max[0]).contains(&pt.x) && (min[1]..=max[1]).contains(&pt.y)
+            }
+            Shape::Polygon { vertices } => polygon_contains(vertices, pt),
+            Shape::HalfPlane { normal, offset } => normal[0] * pt.x + normal[1] * pt.y <= *offset,
+            Shape::All(shapes) => shapes.iter().all(|x| x.contains(pt)),
+            Shape::Any(shapes) => shapes.iter().any(|x| x.contains(pt)),
+            Shape::Not(shape) => !shape.contains(pt),
+        }
+    }
+}
+
+/// Whether the point is on an edge of the polygon, or inside it by the even-odd rule.
+fn polygon_contains(vertices: &[[f64; 2]], pt: &Point) -> bool {
+    let edges = vertices
+        .iter()
+        .zip(vertices.iter().cycle().skip(1))
+        .take(vertices.len());
+    let mut inside = false;
+
+    for (start, end) in edges {
+        let (dx, dy) = (end[0] - start[0], end[1] - start[1]);
+        let cross = dx * (pt.y - start[1]) - dy * (pt.x - start[0]);
+        let within = (start[0].min(end[0])..=start[0].max(end[0])).contains(&pt.x)
+            && (start[1].min(end[1])..=start[1].max(end[1])).contains(&pt.y);
+
+        if cross == 0.0 && within {
+            return true;
+        }
+
+        if (start[1] > pt.y) != (end[1] > pt.y) && pt.x < start[0] + (pt.y - start[1]) / dy * dx {
+            inside = !inside;
+        }
+    }
+
+    inside
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    pub shape: Shape,
+    pub color: String,
+}
+
+/// The rules that colour the points, the first one that matches a point wins and the points
+/// that none of them match get the default colour.
+///
+/// A ruleset is written in TOML:
+///
+/// ```toml
+/// default = "#FF808080"
+///
+/// [[rules]]
+/// color = "#80FF8080"
+/// shape = { circle = { center = [0.0, 0.0], radius = 1.0 } }
+///
+/// [[rules]]
+/// color = "#8080FFFF"
+/// shape = { all = [
+///     { rectangle = { min = [1.0, 1.0], max = [2.0, 2.0] } },
+///     { not = { half_plane = { normal = [1.0, -1.0], offset = 0.0 } } },
+/// ] }
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Ruleset {
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+    pub default: String,
+}
+
+impl Default for Ruleset {
+    /// Green inside the unit circle and red outside of it.
+    fn default() -> Self {
+        Self {
+            rules: vec![Rule {
+                shape: Shape::Circle {
+                    center: [0.0, 0.0],
+                    radius: 1.0,
+                },
+                color: GREEN_TONE.to_string(),
+            }],
+            default: RED_TONE.to_string(),
+        }
+    }
+}
+
+impl Ruleset {
+    pub fn parse(str: &str) -> Result<Self, Box<dyn Error>> {
+        let ruleset: Ruleset = toml::from_str(str)?;
+
+        let colors = ruleset.rules.iter().map(|x| &x.color);
+        if let Some(color) = colors
+            .chain(std::iter::once(&ruleset.default))
+            .find(|x| !is_color(x))
+        {
+            return Err(format!("invalid colour \"{color}\"").into());
+        }
+
+        Ok(ruleset)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let str =
+            std::fs::read_to_string(path).map_err(|err| format!("{}: {err}", path.display()))?;
+
+        Self::parse(&str).map_err(|err| format!("{}: {err}", path.display()).into())
+    }
+
+    pub fn color(&self, pt: &Point) -> &str {
+        self.rules
+            .iter()
+            .find(|x| x.shape.contains(pt))
+            .map_or(&self.default, |x| &x.color)
+    }
+}
+
+pub fn tag_points(pt_list: &[Point], ruleset: &Ruleset) -> Vec<Point> {
     let mut result = Vec::new();
 
     for mut pt in pt_list.iter().cloned() {
-        if pt.magnitude() > 1. {
-            pt.color = RED_TONE.to_string();
-        } else {
-            pt.color = GREEN_TONE.to_string();
-        }
+        pt.color = ruleset.color(&pt).to_string();
         result.push(pt)
     }
 
@@ -64,35 +213,460 @@ pub fn save_points<T: Write>(writer: T, pt_list: &[Point]) -> T {
     writer.into_inner().unwrap()
 }
 
-pub fn load_points(reader: impl Read) -> Vec<Point> {
+/// Draws the points in the style of `svg_gen`, in bounds that fit them all.
+pub fn save_svg(writer: impl Write, pt_list: &[Point]) -> std::io::Result<()> {
+    let bounds = svg_gen::Bounds::fit(pt_list.iter().map(|pt| (pt.x, pt.y)));
+
+    svg_gen::save_points(
+        pt_list.iter().map(|pt| (pt.x, pt.y, pt.color.as_str())),
+        &bounds,
+        writer,
+    )
+}
+
+/// The red, green, blue and alpha of a `#RRGGBB` or `#RRGGBBAA` colour.
+fn parse_color(color: &str) -> Option<tiny_skia::Color> {
+    if !is_color(color) {
+        return None;
+    }
+
+    let channel = |index: usize| u8::from_str_radix(color.get(index..index + 2)?, 16).ok();
+
+    Some(tiny_skia::Color::from_rgba8(
+        channel(1)?,
+        channel(3)?,
+        channel(5)?,
+        channel(7).unwrap_or(u8::MAX),
+    ))
+}
+
+/// Draws the same picture as `save_svg` without the labels of the axes, as a PNG.
+pub fn save_png(mut writer: impl Write, pt_list: &[Point]) -> Result<(), Box<dyn Error>> {
+    use tiny_skia::{Paint, PathBuilder, Pixmap, Stroke, Transform};
+
+    let bounds = svg_gen::Bounds::fit(pt_list.iter().map(|pt| (pt.x, pt.y)));
+    let size = svg_gen::SIZE as f32;
+
+    let mut pixmap = Pixmap::new(size as u32, size as u32).ok_or("the picture is empty")?;
+    pixmap.fill(tiny_skia::Color::from_rgba8(0xEE, 0xEE, 0xEE, 0xFF));
+
+    let mut paint = Paint::default();
+
+    let (axis_x, axis_y) = bounds.axes();
+    let mut axes = PathBuilder::new();
+    axes.move_to(0., axis_y as f32);
+    axes.line_to(size, axis_y as f32);
+    axes.move_to(axis_x as f32, 0.);
+    axes.line_to(axis_x as f32, size);
+
+    if let Some(path) = axes.finish() {
+        paint.set_color(parse_color(svg_gen::AXIS_COLOR).ok_or("invalid axis colour")?);
+        pixmap.stroke_path(
+            &path,
+            &paint,
+            &Stroke::default(),
+            Transform::identity(),
+            None,
+        );
+    }
+
+    for pt in pt_list {
+        let (cx, cy) = bounds.to_picture(pt.x, pt.y);
+        let color =
+            parse_color(&pt.color).ok_or_else(|| format!("invalid colour `{}`", pt.color))?;
+
+        let circle = PathBuilder::from_circle(cx as f32, cy as f32, svg_gen::POINT_RADIUS as f32);
+        if let Some(circle) = circle {
+            paint.set_color(color);
+            pixmap.fill_path(
+                &circle,
+                &paint,
+                tiny_skia::FillRule::Winding,
+                Transform::identity(),
+                None,
+            );
+        }
+    }
+
+    writer.write_all(&pixmap.encode_png()?)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Why a row of the input couldn't be loaded. The rows and columns count from 1.
+#[derive(Debug)]
+pub enum LoadError {
+    /// The CSV reader couldn't read the row at all.
+    Csv { row: usize, error: csv::Error },
+    /// The row doesn't have 2 or 3 columns.
+    ColumnCount { row: usize, count: usize },
+    InvalidNumber {
+        row: usize,
+        column: usize,
+        value: String,
+    },
+    /// The optional third column isn't a `#RRGGBB` or `#RRGGBBAA` colour.
+    InvalidColor {
+        row: usize,
+        column: usize,
+        value: String,
+    },
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoadError::Csv { row, error } => write!(f, "row {row}: {error}"),
+            LoadError::ColumnCount { row, count } => {
+                write!(f, "row {row}: expected 2 or 3 columns, found {count}")
+            }
+            LoadError::InvalidNumber { row, column, value } => {
+                write!(f, "row {row}, column {column}: invalid number \"{value}\"")
+            }
+            LoadError::InvalidColor { row, column, value } => {
+                write!(f, "row {row}, column {column}: invalid colour \"{value}\"")
+            }
+        }
+    }
+}
+
+impl Error for LoadError {}
+
+/// What `load_points` does with the rows it can't load.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum OnError {
+    /// Stop at the first one.
+    #[default]
+    Fail,
+    Skip,
+    /// Skip them, printing the errors to stderr.
+    Report,
+}
+
+fn is_color(value: &str) -> bool {
+    value
+        .strip_prefix('#')
+        .is_some_and(|hex| matches!(hex.len(), 6 | 8) && hex.chars().all(|x| x.is_ascii_hexdigit()))
+}
+
+fn parse_point(row: usize, record: &csv::StringRecord) -> Result<Point, LoadError> {
+    if !(2..=3).contains(&record.len()) {
+        return Err(LoadError::ColumnCount {
+            row,
+            count: record.len(),
+        });
+    }
+
+    let number = |column: usize| {
+        record[column]
+            .parse::<f64>()
+            .map_err(|_| LoadError::InvalidNumber {
+                row,
+                column: column + 1,
+                value: record[column].to_string(),
+            })
+    };
+
+    let mut point = Point::new(number(0)?, number(1)?);
+
+    // the colour that `save_points` writes, so its output can be loaded again
+    match record.get(2) {
+        Some("") | None => {}
+        Some(color) if is_color(color) => point.color = color.to_string(),
+        Some(color) => {
+            return Err(LoadError::InvalidColor {
+                row,
+                column: 3,
+                value: color.to_string(),
+            })
+        }
+    }
+
+    Ok(point)
+}
+
+pub fn load_points(reader: impl Read, on_error: OnError) -> Result<Vec<Point>, LoadError> {
     let mut reader = ReaderBuilder::new()
         .delimiter(b',')
         .has_headers(false)
+        .flexible(true)
         .trim(csv::Trim::All)
         .from_reader(reader);
 
     let mut points = Vec::new();
 
-    for record in reader.records() {
-        let Ok(record) = record else {
-            continue;
+    for (index, record) in reader.records().enumerate() {
+        let row = index + 1;
+        let point = record
+            .map_err(|error| LoadError::Csv { row, error })
+            .and_then(|record| parse_point(row, &record));
+
+        match (point, on_error) {
+            (Ok(point), _) => points.push(point),
+            (Err(err), OnError::Fail) => return Err(err),
+            (Err(_), OnError::Skip) => {}
+            (Err(err), OnError::Report) => eprintln!("{err}"),
+        }
+    }
+
+    Ok(points)
+}
+
+/// How `pointr` writes the tagged points.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Format {
+    #[default]
+    Csv,
+    Svg,
+    Png,
+}
+
+/// The z-score that 95% of the normal distribution is within.
+const Z_95: f64 = 1.959963984540054;
+
+/// A value with its 95% confidence interval.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize)]
+pub struct Estimate {
+    pub value: f64,
+    pub low: f64,
+    pub high: f64,
+}
+
+impl Estimate {
+    /// The proportion of `count` in `total` with its Wilson score interval, which stays in
+    /// `0..=1` for the proportions close to 0 or 1.
+    pub fn proportion(count: usize, total: usize) -> Self {
+        if total == 0 {
+            return Self {
+                value: 0.0,
+                low: 0.0,
+                high: 1.0,
+            };
+        }
+
+        let (count, total) = (count as f64, total as f64);
+        let p = count / total;
+        let z2 = Z_95 * Z_95;
+
+        let denominator = 1.0 + z2 / total;
+        let center = (p + z2 / (2.0 * total)) / denominator;
+        let half = Z_95 * (p * (1.0 - p) / total + z2 / (4.0 * total * total)).sqrt() / denominator;
+
+        Self {
+            value: p,
+            low: (center - half).max(0.0),
+            high: (center + half).min(1.0),
+        }
+    }
+
+    fn scale(self, by: f64) -> Self {
+        Self {
+            value: self.value * by,
+            low: self.low * by,
+            high: self.high * by,
+        }
+    }
+}
+
+/// The distances from each point to the closest other point.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize)]
+pub struct Distances {
+    pub min: f64,
+    pub mean: f64,
+    pub max: f64,
+}
+
+impl Distances {
+    /// `None` for fewer than two points, which have no neighbours.
+    pub fn nearest_neighbours(pt_list: &[&Point]) -> Option<Self> {
+        if pt_list.len() < 2 {
+            return None;
+        }
+
+        let mut sorted = pt_list.iter().map(|pt| (pt.x, pt.y)).collect::<Vec<_>>();
+        sorted.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        // the points further along x than the closest one so far can't be closer
+        let nearest = |index: usize| {
+            let (x, y) = sorted[index];
+            let mut best = f64::INFINITY;
+
+            for &(other_x, other_y) in &sorted[index + 1..] {
+                if other_x - x >= best {
+                    break;
+                }
+                best = best.min((other_x - x).hypot(other_y - y));
+            }
+            for &(other_x, other_y) in sorted[..index].iter().rev() {
+                if x - other_x >= best {
+                    break;
+                }
+                best = best.min((other_x - x).hypot(other_y - y));
+            }
+
+            best
         };
 
-        let x: f64 = record
-            .get(0)
-            .expect("should've had a value for x")
-            .parse::<f64>()
-            .expect("should've been a number");
-        let y: f64 = record
-            .get(1)
-            .expect("should've had a value for y")
-            .parse::<f64>()
-            .expect("should've been a number");
+        let mut distances = Self {
+            min: f64::INFINITY,
+            mean: 0.0,
+            max: 0.0,
+        };
+        for index in 0..sorted.len() {
+            let distance = nearest(index);
+            distances.min = distances.min.min(distance);
+            distances.max = distances.max.max(distance);
+            distances.mean += distance / sorted.len() as f64;
+        }
+
+        Some(distances)
+    }
+}
+
+/// The statistics of the points of one colour.
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize)]
+pub struct TagStats {
+    pub color: String,
+    pub count: usize,
+    /// The proportion of all the points that have the colour.
+    pub ratio: Estimate,
+    pub centroid: [f64; 2],
+    pub min: [f64; 2],
+    pub max: [f64; 2],
+    /// The population variance of x and y.
+    pub variance: [f64; 2],
+    pub nearest_neighbour: Option<Distances>,
+}
+
+impl TagStats {
+    fn new(color: String, pt_list: &[&Point], total: usize) -> Self {
+        let count = pt_list.len();
+        let mut min = [f64::INFINITY; 2];
+        let mut max = [f64::NEG_INFINITY; 2];
+        let mut centroid = [0.0; 2];
 
-        points.push(Point::new(x, y));
+        for pt in pt_list {
+            for (axis, value) in [pt.x, pt.y].into_iter().enumerate() {
+                min[axis] = min[axis].min(value);
+                max[axis] = max[axis].max(value);
+                centroid[axis] += value / count as f64;
+            }
+        }
+
+        let mut variance = [0.0; 2];
+        for pt in pt_list {
+            for (axis, value) in [pt.x, pt.y].into_iter().enumerate() {
+                variance[axis] += (value - centroid[axis]).powi(2) / count as f64;
+            }
+        }
+
+        Self {
+            color,
+            count,
+            ratio: Estimate::proportion(count, total),
+            centroid,
+            min,
+            max,
+            variance,
+            nearest_neighbour: Distances::nearest_neighbours(pt_list),
+        }
     }
+}
 
-    points
+/// The statistics of tagged points, by colour.
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize)]
+pub struct Stats {
+    pub count: usize,
+    /// Sorted by colour.
+    pub tags: Vec<TagStats>,
+    /// Four times the ratio of the `inside` colour, which is π for the points spread evenly over
+    /// the square around the unit circle that are tagged by the default rules. `None` if no
+    /// point has the colour.
+    pub pi: Option<Estimate>,
+}
+
+impl Stats {
+    pub fn new(pt_list: &[Point], inside: &str) -> Self {
+        let mut tags = std::collections::BTreeMap::<&str, Vec<&Point>>::new();
+        for pt in pt_list {
+            tags.entry(&pt.color).or_default().push(pt);
+        }
+
+        let pi = tags
+            .get(inside)
+            .map(|tag| Estimate::proportion(tag.len(), pt_list.len()).scale(4.0));
+
+        Self {
+            count: pt_list.len(),
+            tags: tags
+                .into_iter()
+                .map(|(color, tag)| TagStats::new(color.to_string(), &tag, pt_list.len()))
+                .collect(),
+            pi,
+        }
+    }
+}
+
+/// A table of one row per colour, with the total and π below.
+impl Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut rows = vec![[
+            "color",
+            "count",
+            "ratio (95% CI)",
+            "centroid",
+            "extent",
+            "variance",
+            "nearest neighbour (min/mean/max)",
+        ]
+        .map(String::from)];
+
+        for tag in &self.tags {
+            let ratio = tag.ratio;
+            let nearest = tag.nearest_neighbour.map_or("-".to_string(), |x| {
+                format!("{:.4}/{:.4}/{:.4}", x.min, x.mean, x.max)
+            });
+
+            rows.push([
+                tag.color.clone(),
+                tag.count.to_string(),
+                format!("{:.4} ({:.4}..{:.4})", ratio.value, ratio.low, ratio.high),
+                format!("({:.4}, {:.4})", tag.centroid[0], tag.centroid[1]),
+                format!(
+                    "({:.4}, {:.4})..({:.4}, {:.4})",
+                    tag.min[0], tag.min[1], tag.max[0], tag.max[1]
+                ),
+                format!("({:.4}, {:.4})", tag.variance[0], tag.variance[1]),
+                nearest,
+            ]);
+        }
+
+        let mut widths = [0; 7];
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        for row in &rows {
+            let line = row
+                .iter()
+                .zip(widths)
+                .map(|(cell, width)| format!("{cell:<width$}"))
+                .collect::<Vec<_>>()
+                .join("  ");
+
+            writeln!(f, "{}", line.trim_end())?;
+        }
+
+        writeln!(f, "total: {}", self.count)?;
+        if let Some(pi) = self.pi {
+            writeln!(f, "pi: {:.4} ({:.4}..{:.4})", pi.value, pi.low, pi.high)?;
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -101,47 +675,171 @@ pub enum Input {
     Stdin,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Config {
     pub input: Input,
     pub output: PathBuf,
+    pub on_error: OnError,
+    pub ruleset: Ruleset,
+    pub format: Format,
+}
+
+/// How `pointr stats` prints the statistics.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum StatsFormat {
+    #[default]
+    Table,
+    Json,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StatsConfig {
+    pub input: Input,
+    pub on_error: OnError,
+    pub format: StatsFormat,
+    /// The colour of the points inside the unit circle, for the estimate of π.
+    pub inside: String,
+}
+
+/// What `pointr` was asked to do.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Tag the points and save them.
+    Tag(Config),
+    Stats(StatsConfig),
+}
+
+fn input_arg() -> Arg<'static, 'static> {
+    Arg::with_name("input")
+        .value_name("INPUT")
+        .takes_value(true)
+        .default_value("-")
+        .help("The input file for the program")
 }
 
-impl Config {
+fn on_error_arg() -> Arg<'static, 'static> {
+    Arg::with_name("on-error")
+        .long("on-error")
+        .value_name("POLICY")
+        .takes_value(true)
+        .possible_values(&["fail", "skip", "report"])
+        .default_value("fail")
+        .help("What to do with the rows that can't be loaded")
+}
+
+fn input_of(matches: &ArgMatches) -> Result<Input, Box<dyn Error>> {
+    Ok(match matches.value_of("input").ok_or("`input` expected")? {
+        "-" => Input::Stdin,
+        path => Input::File(path.into()),
+    })
+}
+
+fn on_error_of(matches: &ArgMatches) -> OnError {
+    match matches.value_of("on-error") {
+        Some("skip") => OnError::Skip,
+        Some("report") => OnError::Report,
+        _ => OnError::Fail,
+    }
+}
+
+impl Command {
     pub fn from_args() -> Result<Self, Box<dyn Error>> {
         let matches = App::new("pointr")
             .version("0.1.0")
             .author("66011245@kmitl.ac.th")
             .about("exercise four")
-            .arg(
-                Arg::with_name("input")
-                    .value_name("INPUT")
-                    .takes_value(true)
-                    .default_value("-")
-                    .help("The input file for the program"),
-            )
+            .arg(input_arg())
             .arg(
                 Arg::with_name("output")
                     .value_name("OUTPUT")
                     .takes_value(true)
                     .help("The output file for the program"),
             )
+            .arg(on_error_arg())
+            .arg(
+                Arg::with_name("rules")
+                    .long("rules")
+                    .value_name("FILE")
+                    .takes_value(true)
+                    .help("The TOML file of the rules that colour the points, green inside the unit circle and red outside by default"),
+            )
+            .arg(
+                Arg::with_name("format")
+                    .long("format")
+                    .value_name("FORMAT")
+                    .takes_value(true)
+                    .possible_values(&["csv", "svg", "png"])
+                    .default_value("csv")
+                    .help("The format of the output file"),
+            )
+            .subcommand(
+                SubCommand::with_name("stats")
+                    .about("Prints the statistics of each colour of the tagged points")
+                    .arg(input_arg().help("The CSV of the tagged points"))
+                    .arg(on_error_arg())
+                    .arg(
+                        Arg::with_name("format")
+                            .long("format")
+                            .value_name("FORMAT")
+                            .takes_value(true)
+                            .possible_values(&["table", "json"])
+                            .default_value("table")
+                            .help("How the statistics are printed"),
+                    )
+                    .arg(
+                        Arg::with_name("inside")
+                            .long("inside")
+                            .value_name("COLOR")
+                            .takes_value(true)
+                            .default_value(GREEN_TONE)
+                            .help("The colour of the points inside the unit circle, which estimates pi"),
+                    ),
+            )
             .get_matches();
 
-        let input = match matches.value_of("input").ok_or("`input` expected")? {
-            "-" => Input::Stdin,
-            path => Input::File(path.into()),
-        };
+        if let Some(matches) = matches.subcommand_matches("stats") {
+            let format = match matches.value_of("format") {
+                Some("json") => StatsFormat::Json,
+                _ => StatsFormat::Table,
+            };
+
+            return Ok(Command::Stats(StatsConfig {
+                input: input_of(matches)?,
+                on_error: on_error_of(matches),
+                format,
+                inside: matches.value_of("inside").unwrap_or(GREEN_TONE).to_string(),
+            }));
+        }
 
         let output = PathBuf::from(matches.value_of("output").ok_or("`output` expected")?);
 
-        Ok(Config { input, output })
+        let ruleset = match matches.value_of("rules") {
+            Some(path) => Ruleset::load(path)?,
+            None => Ruleset::default(),
+        };
+
+        let format = match matches.value_of("format") {
+            Some("svg") => Format::Svg,
+            Some("png") => Format::Png,
+            _ => Format::Csv,
+        };
+
+        Ok(Command::Tag(Config {
+            input: input_of(&matches)?,
+            output,
+            on_error: on_error_of(&matches),
+            ruleset,
+            format,
+        }))
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{Point, GREEN_TONE, RED_TONE};
+    use crate::{
+        save_png, save_svg, Distances, Estimate, LoadError, OnError, Point, Ruleset, Shape, Stats,
+        GREEN_TONE, RED_TONE,
+    };
 
     #[test]
     fn tag_points_test() {
@@ -152,7 +850,7 @@ mod tests {
             Point::new(0.5, 0.5),
         ];
 
-        let result = super::tag_points(&point);
+        let result = super::tag_points(&point, &Ruleset::default());
 
         assert_eq!(
             &result,
@@ -184,4 +882,221 @@ mod tests {
 
         assert_eq!(string, EXPECTED_OUTPUT_SAVE_POINTS)
     }
+
+    #[test]
+    fn load_points_test() {
+        let input = "0,0\n1, -1, #FF808080\n0.5,0.5,\n";
+
+        let points = super::load_points(input.as_bytes(), OnError::Fail).unwrap();
+
+        assert_eq!(
+            points,
+            [
+                Point::new(0.0, 0.0),
+                Point::new_with_color(1.0, -1.0, RED_TONE.to_string()),
+                Point::new(0.5, 0.5),
+            ]
+        );
+
+        // what `save_points` writes loads back the same
+        let saved = super::save_points(Vec::new(), &points);
+        assert_eq!(
+            super::load_points(saved.as_slice(), OnError::Fail).unwrap(),
+            points
+        );
+    }
+
+    #[test]
+    fn load_points_errors_test() {
+        let input = "0,0\n1,one\n2\n3,3,red\n4,4\n";
+
+        let err = super::load_points(input.as_bytes(), OnError::Fail).unwrap_err();
+        assert!(matches!(
+            err,
+            LoadError::InvalidNumber { row: 2, column: 2, ref value } if value == "one"
+        ));
+        assert_eq!(err.to_string(), "row 2, column 2: invalid number \"one\"");
+
+        let points = super::load_points(input.as_bytes(), OnError::Skip).unwrap();
+        assert_eq!(points, [Point::new(0.0, 0.0), Point::new(4.0, 4.0)]);
+
+        let err = super::load_points("0,0\n2\n".as_bytes(), OnError::Fail).unwrap_err();
+        assert!(matches!(err, LoadError::ColumnCount { row: 2, count: 1 }));
+
+        let err = super::load_points("3,3,red\n".as_bytes(), OnError::Fail).unwrap_err();
+        assert!(matches!(
+            err,
+            LoadError::InvalidColor { row: 1, column: 3, ref value } if value == "red"
+        ));
+    }
+
+    #[test]
+    fn shapes_test() {
+        let square = Shape::Polygon {
+            vertices: vec![[0.0, 0.0], [2.0, 0.0], [2.0, 2.0], [0.0, 2.0]],
+        };
+        assert!(square.contains(&Point::new(1.0, 1.0)));
+        assert!(square.contains(&Point::new(2.0, 1.0)));
+        assert!(!square.contains(&Point::new(3.0, 1.0)));
+
+        let below_diagonal = Shape::HalfPlane {
+            normal: [-1.0, 1.0],
+            offset: 0.0,
+        };
+        assert!(below_diagonal.contains(&Point::new(1.0, 0.5)));
+        assert!(!below_diagonal.contains(&Point::new(0.5, 1.0)));
+
+        let triangle = Shape::All(vec![square, below_diagonal]);
+        assert!(triangle.contains(&Point::new(1.5, 0.5)));
+        assert!(!triangle.contains(&Point::new(0.5, 1.5)));
+        assert!(Shape::Not(Box::new(triangle)).contains(&Point::new(0.5, 1.5)));
+    }
+
+    #[test]
+    fn ruleset_test() {
+        let ruleset = Ruleset::parse(
+            r##"
+            default = "#000000"
+
+            [[rules]]
+            color = "#FF0000"
+            shape = { rectangle = { min = [0.0, 0.0], max = [1.0, 1.0] } }
+
+            [[rules]]
+            color = "#00FF00"
+            shape = { any = [
+                { circle = { center = [0.0, 0.0], radius = 2.0 } },
+                { half_plane = { normal = [0.0, 1.0], offset = -5.0 } },
+            ] }
+            "##,
+        )
+        .unwrap();
+
+        assert_eq!(ruleset.color(&Point::new(0.5, 0.5)), "#FF0000");
+        assert_eq!(ruleset.color(&Point::new(-1.0, 0.5)), "#00FF00");
+        assert_eq!(ruleset.color(&Point::new(0.0, -6.0)), "#00FF00");
+        assert_eq!(ruleset.color(&Point::new(0.0, 3.0)), "#000000");
+
+        assert!(Ruleset::parse("default = \"green\"").is_err());
+        assert!(Ruleset::parse(
+            "default = \"#000000\"\n[[rules]]\ncolor = \"#000000\"\nshape = { star = {} }"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn save_svg_test() {
+        let points = [
+            Point::new_with_color(0.0, 0.0, "#FF000080".to_string()),
+            Point::new_with_color(10.0, 10.0, "#00FF00".to_string()),
+        ];
+
+        let mut output = Vec::new();
+        save_svg(&mut output, &points).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.starts_with("<svg"));
+        assert!(output.ends_with("</svg>\n"));
+        assert_eq!(output.matches("<circle").count(), 2);
+        assert_eq!(output.matches("<line").count(), 2);
+        assert!(output.contains("fill=\"#FF0000\" fill-opacity=\"0.50"));
+        assert!(output.contains("fill=\"#00FF00\" fill-opacity=\"1\""));
+    }
+
+    #[test]
+    fn save_png_test() {
+        let points = [
+            Point::new_with_color(0.0, 0.0, "#FF0000".to_string()),
+            Point::new_with_color(10.0, 10.0, "#0000FF80".to_string()),
+        ];
+
+        let mut output = Vec::new();
+        save_png(&mut output, &points).unwrap();
+        let pixmap = tiny_skia::Pixmap::decode_png(&output).unwrap();
+
+        assert_eq!((pixmap.width(), pixmap.height()), (500, 500));
+
+        let pixel = |x: u32, y: u32| {
+            let pixel = pixmap.pixel(x, y).unwrap().demultiply();
+            (pixel.red(), pixel.green(), pixel.blue())
+        };
+
+        // the points are a twelfth of the picture away from its edges
+        assert_eq!(pixel(42, 458), (0xFF, 0, 0));
+        // half of the blue over the background
+        let (red, green, blue) = pixel(458, 42);
+        assert!(red == green && red < 0xEE && blue > 0xEE);
+        assert_eq!(pixel(250, 250), (0xEE, 0xEE, 0xEE));
+
+        let invalid = [Point::new_with_color(0.0, 0.0, "red".to_string())];
+        assert!(save_png(Vec::new(), &invalid).is_err());
+    }
+
+    #[test]
+    fn estimate_test() {
+        let half = Estimate::proportion(50, 100);
+        assert_eq!(half.value, 0.5);
+        assert!((half.low - 0.4038).abs() < 1e-4 && (half.high - 0.5962).abs() < 1e-4);
+
+        // stays within the proportions that are possible
+        let none = Estimate::proportion(0, 10);
+        assert_eq!(none.value, 0.0);
+        assert!(none.low.abs() < 1e-12);
+        assert!(none.high > 0.0 && none.high < 1.0);
+
+        let all = Estimate::proportion(10, 10);
+        assert_eq!(all.value, 1.0);
+        assert!(all.low < 1.0 && (all.high - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn nearest_neighbours_test() {
+        let points = [
+            Point::new(0.0, 0.0),
+            Point::new(3.0, 4.0),
+            Point::new(0.0, 1.0),
+            Point::new(10.0, 0.0),
+        ];
+        let points = points.iter().collect::<Vec<_>>();
+
+        let distances = Distances::nearest_neighbours(&points).unwrap();
+        assert_eq!(distances.min, 1.0);
+        assert_eq!(distances.max, 65f64.sqrt());
+        assert_eq!(
+            distances.mean,
+            (1.0 + 18f64.sqrt() + 1.0 + 65f64.sqrt()) / 4.0
+        );
+
+        assert_eq!(Distances::nearest_neighbours(&points[..1]), None);
+    }
+
+    #[test]
+    fn stats_test() {
+        let points = [
+            Point::new_with_color(0.0, 0.0, GREEN_TONE.to_string()),
+            Point::new_with_color(1.0, 2.0, GREEN_TONE.to_string()),
+            Point::new_with_color(5.0, 5.0, RED_TONE.to_string()),
+            Point::new_with_color(-1.0, 1.0, GREEN_TONE.to_string()),
+        ];
+
+        let stats = Stats::new(&points, GREEN_TONE);
+        assert_eq!(stats.count, 4);
+        assert_eq!(stats.pi.unwrap().value, 3.0);
+
+        let [green, red] = &stats.tags[..] else {
+            panic!("expected two tags, found {:?}", stats.tags);
+        };
+
+        assert_eq!((green.color.as_str(), green.count), (GREEN_TONE, 3));
+        assert_eq!(green.ratio.value, 0.75);
+        assert_eq!(green.centroid, [0.0, 1.0]);
+        assert_eq!((green.min, green.max), ([-1.0, 0.0], [1.0, 2.0]));
+        assert_eq!(green.variance, [2.0 / 3.0, 2.0 / 3.0]);
+
+        assert_eq!((red.color.as_str(), red.count), (RED_TONE, 1));
+        assert_eq!(red.variance, [0.0, 0.0]);
+        assert_eq!(red.nearest_neighbour, None);
+
+        assert_eq!(Stats::new(&points, "#000000").pi, None);
+    }
 }
@@ -1,27 +1,31 @@
 use std::{fs::File, io::Read, process::ExitCode};
 
-fn main() -> ExitCode {
-    let config = pointr::Config::from_args();
+fn open(input: &pointr::Input) -> Option<Box<dyn Read>> {
+    match input {
+        pointr::Input::File(file) => match File::open(file) {
+            Ok(file) => Some(Box::new(file)),
+            Err(err) => {
+                eprintln!("{}: {err}", file.display());
+                None
+            }
+        },
+        pointr::Input::Stdin => Some(Box::new(std::io::stdin())),
+    }
+}
 
-    let config = match config {
-        Ok(config) => config,
+fn tag(config: pointr::Config) -> ExitCode {
+    let Some(reader) = open(&config.input) else {
+        return ExitCode::FAILURE;
+    };
+
+    let points = match pointr::load_points(reader, config.on_error) {
+        Ok(points) => points,
         Err(err) => {
             eprintln!("{err}");
             return ExitCode::FAILURE;
         }
     };
 
-    let reader = match config.input {
-        pointr::Input::File(file) => Box::new(match File::open(&file) {
-            Ok(file) => Box::new(file) as Box<dyn Read>,
-            Err(err) => {
-                eprintln!("{}: {err}", file.display());
-                return ExitCode::FAILURE;
-            }
-        }),
-        pointr::Input::Stdin => Box::new(std::io::stdin()) as Box<dyn Read>,
-    };
-
     let writer = match File::create(&config.output) {
         Ok(writer) => writer,
         Err(err) => {
@@ -30,9 +34,63 @@ fn main() -> ExitCode {
         }
     };
 
-    let points = pointr::load_points(reader);
-    let tagged_point = pointr::tag_points(&points);
-    pointr::save_points(writer, &tagged_point);
+    let tagged_point = pointr::tag_points(&points, &config.ruleset);
+    let result = match config.format {
+        pointr::Format::Csv => {
+            pointr::save_points(writer, &tagged_point);
+            Ok(())
+        }
+        pointr::Format::Svg => pointr::save_svg(writer, &tagged_point).map_err(Into::into),
+        pointr::Format::Png => pointr::save_png(writer, &tagged_point),
+    };
+
+    if let Err(err) = result {
+        eprintln!("{}: {err}", config.output.display());
+        return ExitCode::FAILURE;
+    }
+
+    ExitCode::SUCCESS
+}
+
+fn stats(config: pointr::StatsConfig) -> ExitCode {
+    let Some(reader) = open(&config.input) else {
+        return ExitCode::FAILURE;
+    };
+
+    let points = match pointr::load_points(reader, config.on_error) {
+        Ok(points) => points,
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let stats = pointr::Stats::new(&points, &config.inside);
+    match config.format {
+        pointr::StatsFormat::Table => print!("{stats}"),
+        pointr::StatsFormat::Json => match serde_json::to_string_pretty(&stats) {
+            Ok(json) => println!("{json}"),
+            Err(err) => {
+                eprintln!("{err}");
+                return ExitCode::FAILURE;
+            }
+        },
+    }
 
     ExitCode::SUCCESS
 }
+
+fn main() -> ExitCode {
+    let command = match pointr::Command::from_args() {
+        Ok(command) => command,
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match command {
+        pointr::Command::Tag(config) => tag(config),
+        pointr::Command::Stats(config) => stats(config),
+    }
+}
@@ -55,3 +55,170 @@ fn test_invalid() {
         cmd.assert().failure();
     }
 }
+
+#[test]
+fn test_on_error() {
+    let input = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("invalid.csv");
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let output = temp_dir.path().join("output.csv");
+
+    // fails at the first malformed row by default
+    Command::cargo_bin("pointr")
+        .unwrap()
+        .arg(&input)
+        .arg(&output)
+        .assert()
+        .failure()
+        .stderr("row 2, column 2: invalid number \"oops\"\n");
+
+    let expected = std::fs::read_to_string(
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("expected_skip.csv"),
+    )
+    .unwrap();
+
+    Command::cargo_bin("pointr")
+        .unwrap()
+        .arg(&input)
+        .arg(&output)
+        .arg("--on-error=skip")
+        .assert()
+        .success()
+        .stderr("");
+    assert_eq!(std::fs::read_to_string(&output).unwrap(), expected);
+
+    Command::cargo_bin("pointr")
+        .unwrap()
+        .arg(&input)
+        .arg(&output)
+        .arg("--on-error=report")
+        .assert()
+        .success()
+        .stderr(
+            "row 2, column 2: invalid number \"oops\"\n\
+             row 4: expected 2 or 3 columns, found 1\n",
+        );
+    assert_eq!(std::fs::read_to_string(&output).unwrap(), expected);
+}
+
+#[test]
+fn test_rules() {
+    let tests = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let temp_dir = tempfile::tempdir().unwrap();
+    let output = temp_dir.path().join("output.csv");
+
+    // the default rules written out give the same colours as the built-in ones
+    for (input, rules, expected) in [
+        ("input.csv", "default_rules.toml", "expected.csv"),
+        ("quadrants.csv", "quadrants.toml", "expected_quadrants.csv"),
+    ] {
+        Command::cargo_bin("pointr")
+            .unwrap()
+            .arg(tests.join(input))
+            .arg(&output)
+            .arg("--rules")
+            .arg(tests.join(rules))
+            .assert()
+            .success();
+
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            std::fs::read_to_string(tests.join(expected)).unwrap()
+        );
+    }
+
+    // not a ruleset
+    Command::cargo_bin("pointr")
+        .unwrap()
+        .arg(tests.join("input.csv"))
+        .arg(&output)
+        .arg("--rules")
+        .arg(tests.join("input.csv"))
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_format() {
+    let input = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/input.csv");
+    let temp_dir = tempfile::tempdir().unwrap();
+
+    let svg = temp_dir.path().join("output.svg");
+    Command::cargo_bin("pointr")
+        .unwrap()
+        .arg(&input)
+        .arg(&svg)
+        .args(["--format", "svg"])
+        .assert()
+        .success();
+
+    let svg = std::fs::read_to_string(svg).unwrap();
+    assert!(svg.starts_with("<svg"));
+    assert!(svg.contains("fill=\"#80FF80\" fill-opacity=\"0.50"));
+    assert!(svg.contains("fill=\"#FF8080\" fill-opacity=\"0.50"));
+
+    let png = temp_dir.path().join("output.png");
+    Command::cargo_bin("pointr")
+        .unwrap()
+        .arg(&input)
+        .arg(&png)
+        .args(["--format", "png"])
+        .assert()
+        .success();
+
+    assert!(std::fs::read(png)
+        .unwrap()
+        .starts_with(b"\x89PNG\r\n\x1a\n"));
+
+    Command::cargo_bin("pointr")
+        .unwrap()
+        .arg(&input)
+        .arg(temp_dir.path().join("output.gif"))
+        .args(["--format", "gif"])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_stats() {
+    let tests = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests");
+
+    // the output of `pointr` is the input of `pointr stats`
+    Command::cargo_bin("pointr")
+        .unwrap()
+        .arg("stats")
+        .arg(tests.join("expected.csv"))
+        .assert()
+        .success()
+        .stdout(std::fs::read_to_string(tests.join("expected_stats.txt")).unwrap());
+
+    let output = Command::cargo_bin("pointr")
+        .unwrap()
+        .args(["stats", "--format", "json"])
+        .arg(tests.join("expected.csv"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["count"], 3);
+    assert_eq!(json["tags"][0]["color"], "#80FF8080");
+    assert_eq!(json["tags"][0]["count"], 2);
+    assert_eq!(
+        json["tags"][1]["nearest_neighbour"],
+        serde_json::Value::Null
+    );
+
+    // the colours are loaded from the third column
+    Command::cargo_bin("pointr")
+        .unwrap()
+        .arg("stats")
+        .arg(tests.join("input.csv"))
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("#00000000  3"));
+}
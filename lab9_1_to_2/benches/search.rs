@@ -0,0 +1,91 @@
+//! Compares the streaming search with the memory-mapped one on a generated log file.
+//!
+//! Run with `cargo bench -p grepr`, and set `GREPR_BENCH_LINES` to change the size of the file.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+const RUNS: usize = 5;
+const WORDS: &[&str] = &[
+    "GET", "POST", "request", "served", "in", "ms", "user", "session", "cache", "miss", "hit",
+    "timeout", "retry", "upstream", "INFO", "DEBUG", "WARN",
+];
+
+fn generate(path: &Path, lines: usize) -> std::io::Result<()> {
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut output = BufWriter::new(File::create(path)?);
+
+    for number in 0..lines {
+        write!(output, "2023-08-{:02} {number}", rng.gen_range(1..=31))?;
+        for _ in 0..rng.gen_range(4..12) {
+            write!(output, " {}", WORDS[rng.gen_range(0..WORDS.len())])?;
+        }
+        // the lines worth finding are rare, like in a real log
+        if rng.gen_ratio(1, 1000) {
+            write!(output, " ERROR connection reset")?;
+        }
+        writeln!(output)?;
+    }
+
+    output.flush()
+}
+
+/// The median time of running grepr with the arguments, with the output thrown away.
+fn time(args: &[&str]) -> Duration {
+    let mut times = (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            let status = Command::new(env!("CARGO_BIN_EXE_grepr"))
+                .args(args)
+                .stdout(Stdio::null())
+                .status()
+                .expect("grepr runs");
+            assert!(status.code().is_some_and(|x| x < 2), "grepr failed");
+            start.elapsed()
+        })
+        .collect::<Vec<_>>();
+
+    times.sort();
+    times[RUNS / 2]
+}
+
+fn main() -> std::io::Result<()> {
+    let lines = std::env::var("GREPR_BENCH_LINES")
+        .ok()
+        .and_then(|x| x.parse().ok())
+        .unwrap_or(1_000_000);
+
+    let path = std::env::temp_dir().join(format!("grepr-bench-{}.log", std::process::id()));
+    generate(&path, lines)?;
+    let size = std::fs::metadata(&path)?.len();
+    let file = path.to_str().expect("the temporary path is UTF-8");
+
+    println!("{lines} lines, {} MiB", size / (1024 * 1024));
+    println!("{:<32} {:>12} {:>12}", "search", "streaming", "mmap");
+
+    let cases: &[(&str, &[&str])] = &[
+        ("rare literal", &["ERROR"]),
+        ("rare literal, count", &["-c", "ERROR"]),
+        ("common literal, count", &["-c", "GET"]),
+        ("regex, line numbers", &["-n", "reset$"]),
+        ("case-insensitive words", &["-i", "-w", "error"]),
+        ("fixed strings", &["-F", "-e", "timeout", "-e", "ERROR"]),
+        ("inverted, count", &["-c", "-v", "GET"]),
+    ];
+
+    for (name, args) in cases {
+        let streaming = time(&[args, &[file][..]].concat());
+        let mmap = time(&[&["--mmap"][..], args, &[file][..]].concat());
+
+        println!("{name:<32} {streaming:>12.2?} {mmap:>12.2?}");
+    }
+
+    std::fs::remove_file(&path)
+}
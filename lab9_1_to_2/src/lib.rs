@@ -1,11 +1,17 @@
 use std::{
+    collections::{BTreeMap, VecDeque},
     error::Error,
-    io::{BufRead, BufReader},
+    fmt::{Debug, Display},
+    io::{BufRead, BufReader, BufWriter, IsTerminal, Write},
+    ops::{ControlFlow, Range},
     path::{Path, PathBuf},
+    sync::{mpsc, Arc, Condvar, Mutex, MutexGuard},
 };
 
+use aho_corasick::{AhoCorasick, AhoCorasickBuilder, MatchKind};
 use clap::{App, Arg};
-use regex::{Regex, RegexBuilder};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use regex::bytes::{Regex, RegexBuilder};
 use walkdir::WalkDir;
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -14,13 +20,654 @@ pub enum Input {
     Files(Vec<PathBuf>),
 }
 
+/// The number of lines printed around every match.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Context {
+    pub before: usize,
+    pub after: usize,
+    /// Whether it was given, even as `-C0`, which still separates the groups of lines with `--`.
+    pub given: bool,
+}
+
+impl Context {
+    pub fn is_enabled(&self) -> bool {
+        self.given || self.before != 0 || self.after != 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HitKind {
+    /// The line matched the pattern (or didn't, with `--invert-match`).
+    Match,
+    /// The line is printed only because it's near a match.
+    Context,
+}
+
+/// A line found by [`find_lines`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Hit {
+    pub kind: HitKind,
+
+    /// One-based.
+    pub line_number: usize,
+
+    /// The offset of the first byte of the line from the start of the input.
+    pub byte_offset: usize,
+
+    /// The line without its terminator, which may not be valid UTF-8.
+    pub line: Vec<u8>,
+
+    /// The byte ranges of `line` that matched the pattern, which are none for the lines found with
+    /// `--invert-match`.
+    pub spans: Vec<Range<usize>>,
+}
+
+/// The SGR sequences used to highlight the output, configured by the `GREP_COLORS` environment
+/// variable in the same format as GNU grep.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Colors {
+    /// `ms`, the matched text in a selected line.
+    pub selected_match: String,
+    /// `mc`, the matched text in a context line.
+    pub context_match: String,
+    /// `sl`, the rest of a selected line.
+    pub selected_line: String,
+    /// `cx`, the rest of a context line.
+    pub context_line: String,
+    /// `fn`
+    pub filename: String,
+    /// `ln`
+    pub line_number: String,
+    /// `bn`
+    pub byte_offset: String,
+    /// `se`, the `:` and `-` after the prefixes and the `--` between the groups of lines.
+    pub separator: String,
+    /// Cleared by `ne`; erases to the end of the line after every colored text.
+    pub erase_line: bool,
+}
+
+impl Default for Colors {
+    fn default() -> Self {
+        Self {
+            selected_match: "01;31".to_string(),
+            context_match: "01;31".to_string(),
+            selected_line: String::new(),
+            context_line: String::new(),
+            filename: "35".to_string(),
+            line_number: "32".to_string(),
+            byte_offset: "32".to_string(),
+            separator: "36".to_string(),
+            erase_line: true,
+        }
+    }
+}
+
+impl Colors {
+    /// Parses the value of `GREP_COLORS`, such as `ms=01;31:fn=35:ne`, on top of the default
+    /// colors. Unknown capabilities are ignored like GNU grep does.
+    pub fn from_grep_colors(value: &str) -> Self {
+        let mut colors = Self::default();
+
+        for capability in value.split(':') {
+            let (name, sgr) = match capability.split_once('=') {
+                Some((name, sgr)) => (name, sgr.to_string()),
+                None => (capability, String::new()),
+            };
+
+            match name {
+                "mt" => {
+                    colors.selected_match = sgr.clone();
+                    colors.context_match = sgr;
+                }
+                "ms" => colors.selected_match = sgr,
+                "mc" => colors.context_match = sgr,
+                "sl" => colors.selected_line = sgr,
+                "cx" => colors.context_line = sgr,
+                "fn" => colors.filename = sgr,
+                "ln" => colors.line_number = sgr,
+                "bn" => colors.byte_offset = sgr,
+                "se" => colors.separator = sgr,
+                "ne" => colors.erase_line = false,
+                _ => {}
+            }
+        }
+
+        colors
+    }
+
+    fn match_sgr(&self, kind: HitKind) -> &str {
+        match kind {
+            HitKind::Match => &self.selected_match,
+            HitKind::Context => &self.context_match,
+        }
+    }
+
+    fn line_sgr(&self, kind: HitKind) -> &str {
+        match kind {
+            HitKind::Match => &self.selected_line,
+            HitKind::Context => &self.context_line,
+        }
+    }
+
+    fn write(&self, output: &mut impl Write, text: &[u8], sgr: &str) -> std::io::Result<()> {
+        if sgr.is_empty() {
+            return output.write_all(text);
+        }
+
+        let erase = if self.erase_line { "\x1b[K" } else { "" };
+        write!(output, "\x1b[{sgr}m{erase}")?;
+        output.write_all(text)?;
+        write!(output, "\x1b[m{erase}")
+    }
+}
+
+/// Highlights the raw bytes of `text` when the colors are enabled.
+fn write_colored_bytes(
+    output: &mut impl Write,
+    colors: Option<&Colors>,
+    text: &[u8],
+    sgr: impl Fn(&Colors) -> &str,
+) -> std::io::Result<()> {
+    match colors {
+        Some(colors) => colors.write(output, text, sgr(colors)),
+        None => output.write_all(text),
+    }
+}
+
+/// Highlights `text` when the colors are enabled.
+fn write_colored(
+    output: &mut impl Write,
+    colors: Option<&Colors>,
+    text: impl Display,
+    sgr: impl Fn(&Colors) -> &str,
+) -> std::io::Result<()> {
+    write_colored_bytes(output, colors, text.to_string().as_bytes(), sgr)
+}
+
+/// Finds the parts of a line that match the search patterns, whether they're regular expressions,
+/// fixed strings or anything else. The lines are bytes since the files aren't always UTF-8.
+pub trait Matcher: Debug + Send + Sync {
+    /// Returns the byte ranges of `line` that match, in order and without overlapping. The line
+    /// matches when there's at least one range, even an empty one.
+    fn find_spans(&self, line: &[u8]) -> Vec<Range<usize>>;
+
+    fn is_match(&self, line: &[u8]) -> bool {
+        !self.find_spans(line).is_empty()
+    }
+
+    /// Returns where a match may start in a buffer of many lines, so that the lines before it
+    /// don't have to be checked one by one. The candidate may cross lines as long as no line
+    /// before it matches, and `None` means none of them does. The default can't skip anything.
+    fn find_candidate(&self, buffer: &[u8]) -> Option<usize> {
+        let _ = buffer;
+        Some(0)
+    }
+
+    /// Whether `find_candidate` can search a buffer of many lines at once, or the lines have to
+    /// be read one by one.
+    fn finds_candidates(&self) -> bool {
+        true
+    }
+}
+
+impl Matcher for Regex {
+    fn find_spans(&self, line: &[u8]) -> Vec<Range<usize>> {
+        self.find_iter(line).map(|x| x.range()).collect()
+    }
+
+    fn is_match(&self, line: &[u8]) -> bool {
+        Regex::is_match(self, line)
+    }
+}
+
+/// Which part of a line a match has to cover.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MatchMode {
+    /// Anywhere in the line.
+    #[default]
+    Substring,
+    /// `--word-regexp`, a match that isn't preceded or followed by a word character.
+    Word,
+    /// `--line-regexp`, the whole line.
+    Line,
+}
+
+/// The word constituents of GNU grep: letters, digits and the underscore.
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Decodes the character that starts `bytes`, `None` if it isn't valid UTF-8.
+fn first_char(bytes: &[u8]) -> Option<char> {
+    let bytes = &bytes[..bytes.len().min(4)];
+    let valid = match std::str::from_utf8(bytes) {
+        Ok(valid) => valid,
+        Err(err) => std::str::from_utf8(&bytes[..err.valid_up_to()]).expect("it's valid"),
+    };
+
+    valid.chars().next()
+}
+
+/// Decodes the character that ends `bytes`, `None` if it isn't valid UTF-8.
+fn last_char(bytes: &[u8]) -> Option<char> {
+    (1..=bytes.len().min(4))
+        .find_map(|len| std::str::from_utf8(&bytes[bytes.len() - len..]).ok())
+        .and_then(|x| x.chars().next_back())
+}
+
+/// Collects the matches that fit `mode`, given a function that finds the leftmost match starting
+/// at or after a byte offset of `line`.
+fn find_spans_in_mode(
+    line: &[u8],
+    mode: MatchMode,
+    find_at: impl Fn(usize) -> Option<Range<usize>>,
+) -> Vec<Range<usize>> {
+    let mut spans = Vec::new();
+    let mut start = 0;
+
+    while start <= line.len() {
+        let Some(span) = find_at(start) else {
+            break;
+        };
+
+        let fits = match mode {
+            MatchMode::Substring => true,
+            MatchMode::Word => {
+                !last_char(&line[..span.start]).is_some_and(is_word_char)
+                    && !first_char(&line[span.end..]).is_some_and(is_word_char)
+            }
+            MatchMode::Line => span == (0..line.len()),
+        };
+
+        // continue after the match, or from the next character when there's nothing to skip
+        start = if fits && !span.is_empty() {
+            span.end
+        } else {
+            span.start + first_char(&line[span.start..]).map_or(1, char::len_utf8)
+        };
+
+        if fits {
+            spans.push(span);
+        }
+    }
+
+    spans
+}
+
+/// Whether the pattern has `\A` or `\z`, or turns off the multi-line mode, so that it matches
+/// at the start or end of the whole text, which are different in a buffer of many lines.
+fn has_text_anchors(pattern: &str) -> bool {
+    let mut chars = pattern.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if matches!(chars.next(), Some('A' | 'z')) {
+                    return true;
+                }
+            }
+            // the flags of a group like `(?-m)` or `(?i-m:...)`
+            '(' if chars.peek() == Some(&'?') => {
+                let flags = chars
+                    .by_ref()
+                    .take_while(|x| !matches!(x, ')' | ':'))
+                    .collect::<String>();
+
+                if flags.split_once('-').is_some_and(|x| x.1.contains('m')) {
+                    return true;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    false
+}
+
+/// Matches any of the patterns as a regular expression.
 #[derive(Debug, Clone)]
+pub struct RegexMatcher {
+    regex: Regex,
+
+    /// The same regex where `^` and `$` match around every line, to find the candidates. `None`
+    /// for the patterns anchored at the start or end of the whole text.
+    buffer_regex: Option<Regex>,
+
+    /// The regexes without Unicode, where `.` or `\w` match a single byte, for the lines that
+    /// aren't UTF-8 like the Latin-1 ones. `None` for the patterns that need Unicode, like `\p{L}`.
+    bytes: Option<(Regex, Option<Regex>)>,
+
+    mode: MatchMode,
+}
+
+impl RegexMatcher {
+    pub fn new(
+        patterns: &[impl AsRef<str>],
+        case_insensitive: bool,
+        mode: MatchMode,
+    ) -> Result<Self, Box<dyn Error>> {
+        // every pattern is checked on its own by the same parser that compiles it, since a
+        // pattern like `a)|(b` would become valid once joined with the others
+        for pattern in patterns.iter().map(AsRef::as_ref) {
+            RegexBuilder::new(pattern)
+                .build()
+                .map_err(|_| format!("Invalid pattern \"{pattern}\""))?;
+        }
+
+        let alternatives = if patterns.is_empty() {
+            // no patterns match nothing, like `grep -f /dev/null`
+            r"[^\s\S]".to_string()
+        } else {
+            patterns
+                .iter()
+                .map(|x| format!("(?:{})", x.as_ref()))
+                .collect::<Vec<_>>()
+                .join("|")
+        };
+
+        let pattern = match mode {
+            MatchMode::Substring => alternatives,
+            // the word is the first group, between the characters that aren't part of a word, so
+            // that a pattern like `ab|abc` can still match the whole of "abc"
+            MatchMode::Word => format!(r"(?:\W|^)({alternatives})(?:\W|$)"),
+            MatchMode::Line => format!("^(?:{alternatives})$"),
+        };
+
+        let anchored = patterns.iter().any(|x| has_text_anchors(x.as_ref()));
+        let build = |unicode| -> Result<(Regex, Option<Regex>), regex::Error> {
+            let regex = RegexBuilder::new(&pattern)
+                .case_insensitive(case_insensitive)
+                .unicode(unicode)
+                .build()?;
+            let buffer_regex = if anchored {
+                None
+            } else {
+                let regex = RegexBuilder::new(&pattern)
+                    .case_insensitive(case_insensitive)
+                    .unicode(unicode)
+                    .multi_line(true)
+                    .crlf(true)
+                    .build()?;
+
+                Some(regex)
+            };
+
+            Ok((regex, buffer_regex))
+        };
+
+        let (regex, buffer_regex) = build(true)?;
+
+        Ok(Self {
+            regex,
+            buffer_regex,
+            bytes: build(false).ok(),
+            mode,
+        })
+    }
+
+    /// Gets the regex for `line`, the one without Unicode when it isn't UTF-8.
+    fn regex_for(&self, line: &[u8]) -> (&Regex, bool) {
+        match &self.bytes {
+            Some((regex, _)) if std::str::from_utf8(line).is_err() => (regex, false),
+            _ => (&self.regex, true),
+        }
+    }
+}
+
+impl RegexMatcher {
+    /// Finds the leftmost word of `--word-regexp` that starts at or after `start`. The regex takes
+    /// the character before the word too, so the search starts from that one.
+    fn find_word_at(
+        regex: &Regex,
+        unicode: bool,
+        line: &[u8],
+        start: usize,
+    ) -> Option<Range<usize>> {
+        let before = match last_char(&line[..start]) {
+            Some(c) if unicode => c.len_utf8(),
+            _ => start.min(1),
+        };
+        let word = regex.captures_at(line, start - before)?.get(1)?.range();
+
+        if word.start >= start {
+            Some(word)
+        } else {
+            // `^` took the place of the character before `start`, which is then part of a word
+            let word = regex.captures_at(line, start)?.get(1)?.range();
+            Some(word)
+        }
+    }
+}
+
+impl Matcher for RegexMatcher {
+    fn find_spans(&self, line: &[u8]) -> Vec<Range<usize>> {
+        let (regex, unicode) = self.regex_for(line);
+
+        match self.mode {
+            MatchMode::Substring | MatchMode::Line => regex.find_spans(line),
+            MatchMode::Word => {
+                let mut spans = Vec::new();
+                let mut start = 0;
+
+                while let Some(word) = Self::find_word_at(regex, unicode, line, start) {
+                    start = if word.is_empty() {
+                        word.start + first_char(&line[word.start..]).map_or(1, char::len_utf8)
+                    } else {
+                        word.end
+                    };
+                    spans.push(word);
+
+                    if start > line.len() {
+                        break;
+                    }
+                }
+
+                spans
+            }
+        }
+    }
+
+    fn is_match(&self, line: &[u8]) -> bool {
+        self.regex_for(line).0.is_match(line)
+    }
+
+    fn find_candidate(&self, buffer: &[u8]) -> Option<usize> {
+        let Some(regex) = &self.buffer_regex else {
+            return Some(0);
+        };
+
+        let candidate = regex.find(buffer).map(|x| x.start());
+        let searched = &buffer[..candidate.unwrap_or(buffer.len())];
+
+        // the lines before the candidate that aren't UTF-8 may still match without Unicode
+        match &self.bytes {
+            Some((_, Some(bytes_regex))) if std::str::from_utf8(searched).is_err() => {
+                match (candidate, bytes_regex.find(buffer).map(|x| x.start())) {
+                    (Some(candidate), Some(bytes_candidate)) => {
+                        Some(candidate.min(bytes_candidate))
+                    }
+                    (candidate, bytes_candidate) => candidate.or(bytes_candidate),
+                }
+            }
+            _ => candidate,
+        }
+    }
+
+    fn finds_candidates(&self) -> bool {
+        self.buffer_regex.is_some()
+    }
+}
+
+/// Matches any of the patterns as a fixed string with an Aho-Corasick automaton, which stays fast
+/// for the long lists of patterns that would make a regular expression too big. The longest
+/// pattern wins among the ones starting at the same place.
+#[derive(Debug, Clone)]
+pub struct FixedMatcher {
+    automaton: AhoCorasick,
+    mode: MatchMode,
+}
+
+impl FixedMatcher {
+    /// The case-insensitive search only folds ASCII letters, see [`new_matcher`] for the rest.
+    pub fn new(
+        patterns: &[impl AsRef<str>],
+        case_insensitive: bool,
+        mode: MatchMode,
+    ) -> Result<Self, Box<dyn Error>> {
+        let automaton = AhoCorasickBuilder::new()
+            .match_kind(MatchKind::LeftmostLongest)
+            .ascii_case_insensitive(case_insensitive)
+            .build(patterns.iter().map(AsRef::as_ref))?;
+
+        Ok(Self { automaton, mode })
+    }
+}
+
+impl Matcher for FixedMatcher {
+    fn find_spans(&self, line: &[u8]) -> Vec<Range<usize>> {
+        find_spans_in_mode(line, self.mode, |start| {
+            self.automaton
+                .find(aho_corasick::Input::new(line).span(start..line.len()))
+                .map(|x| x.range())
+        })
+    }
+
+    fn find_candidate(&self, buffer: &[u8]) -> Option<usize> {
+        // the patterns are lines themselves, so they can't cross lines
+        self.automaton.find(buffer).map(|x| x.start())
+    }
+}
+
+/// Builds the matcher for `patterns`, read as fixed strings or as regular expressions.
+pub fn new_matcher(
+    patterns: &[impl AsRef<str>],
+    fixed_strings: bool,
+    case_insensitive: bool,
+    mode: MatchMode,
+) -> Result<Box<dyn Matcher>, Box<dyn Error>> {
+    // the automaton only folds ASCII, so the other fixed strings become escaped regexes
+    let ascii_only = !case_insensitive || patterns.iter().all(|x| x.as_ref().is_ascii());
+
+    Ok(match (fixed_strings, ascii_only) {
+        (true, true) => Box::new(FixedMatcher::new(patterns, case_insensitive, mode)?),
+        (true, false) => {
+            let escaped = patterns
+                .iter()
+                .map(|x| regex::escape(x.as_ref()))
+                .collect::<Vec<_>>();
+            Box::new(RegexMatcher::new(&escaped, case_insensitive, mode)?)
+        }
+        (false, _) => Box::new(RegexMatcher::new(patterns, case_insensitive, mode)?),
+    })
+}
+
+/// Reads one pattern per line from `path`, or from stdin when it's `-`.
+fn read_patterns(path: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let content = if path == "-" {
+        std::io::read_to_string(std::io::stdin())
+    } else {
+        std::fs::read_to_string(path)
+    }
+    .map_err(|err| format!("{path}: {err}"))?;
+
+    Ok(content.lines().map(ToString::to_string).collect())
+}
+
+/// The globs that choose the files to search by their base names, like GNU grep does.
+#[derive(Debug, Clone, Default)]
+pub struct FileFilter {
+    /// `--include`, only the matching files are searched unless it's empty.
+    pub include: GlobSet,
+    /// `--exclude`
+    pub exclude: GlobSet,
+    /// `--exclude-dir`, the matching directories are skipped by the recursive search.
+    pub exclude_dir: GlobSet,
+}
+
+fn is_name_match(globs: &GlobSet, path: &Path) -> bool {
+    path.file_name().is_some_and(|x| globs.is_match(x))
+}
+
+impl FileFilter {
+    pub fn is_file_included(&self, path: &Path) -> bool {
+        (self.include.is_empty() || is_name_match(&self.include, path))
+            && !is_name_match(&self.exclude, path)
+    }
+
+    pub fn is_dir_included(&self, path: &Path) -> bool {
+        !is_name_match(&self.exclude_dir, path)
+    }
+}
+
+fn build_glob_set<'a>(globs: impl Iterator<Item = &'a str>) -> Result<GlobSet, Box<dyn Error>> {
+    let mut builder = GlobSetBuilder::new();
+
+    for glob in globs {
+        builder.add(Glob::new(glob).map_err(|err| format!("{glob}: {err}"))?);
+    }
+
+    Ok(builder.build()?)
+}
+
+/// How the binary files, the ones with a NUL byte near the start, are searched.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BinaryFiles {
+    /// Only tell whether the file matches.
+    #[default]
+    Binary,
+    /// `--text`, search the file like any other.
+    Text,
+    /// `-I`, skip the file.
+    WithoutMatch,
+}
+
+#[derive(Debug)]
 pub struct Config {
     pub input: Input,
     pub recursive: bool,
-    pub pattern: Regex,
+    pub matcher: Box<dyn Matcher>,
     pub count: bool,
     pub invert_match: bool,
+    pub line_number: bool,
+    pub byte_offset: bool,
+    pub context: Context,
+    pub only_matching: bool,
+    pub binary_files: BinaryFiles,
+    pub filter: FileFilter,
+
+    /// Whether the files are searched in the order of their paths, rather than the order they're
+    /// given and found in the directories.
+    pub sort_by_path: bool,
+
+    /// The number of files searched at the same time.
+    pub threads: usize,
+
+    /// `-q`, print nothing and stop at the first match.
+    pub quiet: bool,
+    pub files_with_matches: bool,
+    pub files_without_match: bool,
+
+    /// The number of matches after which a file isn't read anymore.
+    pub max_count: Option<usize>,
+
+    /// `-s`, don't print the errors about the files that can't be read.
+    pub no_messages: bool,
+
+    /// Whether the files are searched through memory maps, which is faster for the big files but
+    /// crashes if they're truncated during the search.
+    pub mmap: bool,
+
+    /// `None` when the output isn't colored.
+    pub colors: Option<Colors>,
+}
+
+fn parse_context_value(value: Option<&str>) -> Result<Option<usize>, Box<dyn Error>> {
+    value
+        .map(|x| {
+            x.parse::<usize>()
+                .map_err(|_| format!("{x}: invalid context length argument").into())
+        })
+        .transpose()
 }
 
 impl Config {
@@ -33,16 +680,54 @@ impl Config {
                 Arg::with_name("pattern")
                     .value_name("PATTERN")
                     .takes_value(true)
-                    .required(true)
-                    .help("Search pattern"),
+                    .required_unless_one(&["regexp", "pattern-file"])
+                    .help("Search pattern, read as the first file when -e or -f is given"),
             )
             .arg(
                 Arg::with_name("file")
                     .value_name("FILE")
                     .takes_value(true)
                     .multiple(true)
-                    .default_value("-")
-                    .help("Input file(s)"),
+                    .help("Input file(s) [default: -]"),
+            )
+            .arg(
+                Arg::with_name("regexp")
+                    .short("e")
+                    .long("regexp")
+                    .value_name("PATTERN")
+                    .takes_value(true)
+                    .multiple(true)
+                    .number_of_values(1)
+                    .allow_hyphen_values(true)
+                    .help("Search for PATTERN, can be repeated"),
+            )
+            .arg(
+                Arg::with_name("pattern-file")
+                    .short("f")
+                    .long("file")
+                    .value_name("FILE")
+                    .takes_value(true)
+                    .multiple(true)
+                    .number_of_values(1)
+                    .help("Read the patterns from FILE, one per line"),
+            )
+            .arg(
+                Arg::with_name("fixed-strings")
+                    .short("F")
+                    .long("fixed-strings")
+                    .help("Read the patterns as fixed strings instead of regexes"),
+            )
+            .arg(
+                Arg::with_name("word-regexp")
+                    .short("w")
+                    .long("word-regexp")
+                    .help("Only match whole words"),
+            )
+            .arg(
+                Arg::with_name("line-regexp")
+                    .short("x")
+                    .long("line-regexp")
+                    .help("Only match whole lines"),
             )
             .arg(
                 Arg::with_name("count")
@@ -68,192 +753,1296 @@ impl Config {
                     .long("recursive")
                     .help("Recusive search"),
             )
-            .get_matches();
+            .arg(
+                Arg::with_name("line-number")
+                    .short("n")
+                    .long("line-number")
+                    .help("Print the line number of each line"),
+            )
+            .arg(
+                Arg::with_name("byte-offset")
+                    .short("b")
+                    .long("byte-offset")
+                    .help("Print the byte offset of each line"),
+            )
+            .arg(
+                Arg::with_name("after-context")
+                    .short("A")
+                    .long("after-context")
+                    .value_name("NUM")
+                    .takes_value(true)
+                    .help("Print NUM lines after each match"),
+            )
+            .arg(
+                Arg::with_name("before-context")
+                    .short("B")
+                    .long("before-context")
+                    .value_name("NUM")
+                    .takes_value(true)
+                    .help("Print NUM lines before each match"),
+            )
+            .arg(
+                Arg::with_name("context")
+                    .short("C")
+                    .long("context")
+                    .value_name("NUM")
+                    .takes_value(true)
+                    .help("Print NUM lines before and after each match"),
+            )
+            .arg(
+                Arg::with_name("only-matching")
+                    .short("o")
+                    .long("only-matching")
+                    .help("Print only the matched parts of the lines, one per line"),
+            )
+            .arg(
+                Arg::with_name("color")
+                    .long("color")
+                    .alias("colour")
+                    .value_name("WHEN")
+                    .takes_value(true)
+                    .min_values(0)
+                    .require_equals(true)
+                    .possible_values(&["auto", "always", "never"])
+                    .help(
+                        "Highlight the matches: always, never or auto (only when the output is a \
+                         terminal, the default when WHEN is omitted); the colors are taken from \
+                         GREP_COLORS",
+                    ),
+            )
+            .arg(
+                Arg::with_name("text")
+                    .short("a")
+                    .long("text")
+                    .help("Search the binary files as if they were text"),
+            )
+            .arg(
+                Arg::with_name("skip-binary")
+                    .short("I")
+                    .help("Skip the binary files"),
+            )
+            .arg(
+                Arg::with_name("include")
+                    .long("include")
+                    .value_name("GLOB")
+                    .takes_value(true)
+                    .multiple(true)
+                    .number_of_values(1)
+                    .help("Search only the files whose base name matches GLOB"),
+            )
+            .arg(
+                Arg::with_name("exclude")
+                    .long("exclude")
+                    .value_name("GLOB")
+                    .takes_value(true)
+                    .multiple(true)
+                    .number_of_values(1)
+                    .help("Skip the files whose base name matches GLOB"),
+            )
+            .arg(
+                Arg::with_name("exclude-dir")
+                    .long("exclude-dir")
+                    .value_name("GLOB")
+                    .takes_value(true)
+                    .multiple(true)
+                    .number_of_values(1)
+                    .help("Skip the directories whose base name matches GLOB when recursing"),
+            )
+            .arg(
+                Arg::with_name("sort")
+                    .long("sort")
+                    .value_name("KEY")
+                    .takes_value(true)
+                    .possible_values(&["path"])
+                    .help("Print the files in the order of their paths"),
+            )
+            .arg(
+                Arg::with_name("threads")
+                    .short("j")
+                    .long("threads")
+                    .value_name("NUM")
+                    .takes_value(true)
+                    .help("Search NUM files at the same time [default: the number of CPUs]"),
+            )
+            .arg(
+                Arg::with_name("quiet")
+                    .short("q")
+                    .long("quiet")
+                    .alias("silent")
+                    .help("Print nothing, exit with zero as soon as anything matches"),
+            )
+            .arg(
+                Arg::with_name("files-with-matches")
+                    .short("l")
+                    .long("files-with-matches")
+                    .help("Print only the names of the files with matches"),
+            )
+            .arg(
+                Arg::with_name("files-without-match")
+                    .short("L")
+                    .long("files-without-match")
+                    .overrides_with("files-with-matches")
+                    .help("Print only the names of the files without matches"),
+            )
+            .arg(
+                Arg::with_name("max-count")
+                    .short("m")
+                    .long("max-count")
+                    .value_name("NUM")
+                    .takes_value(true)
+                    .help("Stop reading a file after NUM matching lines"),
+            )
+            .arg(
+                Arg::with_name("no-messages")
+                    .short("s")
+                    .long("no-messages")
+                    .help("Suppress the errors about nonexistent or unreadable files"),
+            )
+            .arg(
+                Arg::with_name("mmap")
+                    .long("mmap")
+                    .help("Search the files through memory maps, which is faster for big files but crashes if they're truncated during the search"),
+            )
+            .get_matches_safe()
+            .unwrap_or_else(|err| {
+                // GNU grep exits with 2 on the usage errors, but the help is still a success
+                if err.use_stderr() {
+                    eprintln!("{}", err.message);
+                    std::process::exit(2);
+                }
+                err.exit()
+            });
+
+        let mut patterns = Vec::new();
+        let mut files = matches.values_of_lossy("file").unwrap_or_default();
+
+        if matches.is_present("regexp") || matches.is_present("pattern-file") {
+            // the positional pattern is actually the first file
+            if let Some(file) = matches.value_of("pattern") {
+                files.insert(0, file.to_string());
+            }
+
+            for pattern in matches.values_of("regexp").into_iter().flatten() {
+                patterns.extend(pattern.split('\n').map(ToString::to_string));
+            }
+            for path in matches.values_of("pattern-file").into_iter().flatten() {
+                patterns.extend(read_patterns(path)?);
+            }
+        } else {
+            let pattern = matches.value_of("pattern").expect("it's required argument");
+            patterns.extend(pattern.split('\n').map(ToString::to_string));
+        }
+
+        if files.is_empty() {
+            files.push("-".to_string());
+        }
+
+        // -x takes precedence over -w like in GNU grep
+        let mode = if matches.is_present("line-regexp") {
+            MatchMode::Line
+        } else if matches.is_present("word-regexp") {
+            MatchMode::Word
+        } else {
+            MatchMode::Substring
+        };
+
+        let matcher = new_matcher(
+            &patterns,
+            matches.is_present("fixed-strings"),
+            matches.is_present("insensitive"),
+            mode,
+        )?;
+
+        let input = if let (Some("-"), 1) = (files.first().map(AsRef::as_ref), files.len()) {
+            Input::Stdin
+        } else {
+            Input::Files(files.into_iter().map(PathBuf::from).collect())
+        };
+
+        // -A and -B take precedence over -C
+        let context = parse_context_value(matches.value_of("context"))?.unwrap_or(0);
+        let context = Context {
+            before: parse_context_value(matches.value_of("before-context"))?.unwrap_or(context),
+            after: parse_context_value(matches.value_of("after-context"))?.unwrap_or(context),
+            given: ["context", "before-context", "after-context"]
+                .iter()
+                .any(|x| matches.is_present(x)),
+        };
+
+        // the context lines are useless for counting and printing only the matched parts
+        let context = if matches.is_present("count") || matches.is_present("only-matching") {
+            Context::default()
+        } else {
+            context
+        };
+
+        let color = match (matches.occurrences_of("color"), matches.value_of("color")) {
+            (0, _) | (_, Some("never")) => false,
+            (_, Some("always")) => true,
+            _ => std::io::stdout().is_terminal(),
+        };
+
+        let filter = FileFilter {
+            include: build_glob_set(matches.values_of("include").into_iter().flatten())?,
+            exclude: build_glob_set(matches.values_of("exclude").into_iter().flatten())?,
+            exclude_dir: build_glob_set(matches.values_of("exclude-dir").into_iter().flatten())?,
+        };
+
+        let threads = match matches.value_of("threads") {
+            Some(x) => x
+                .parse::<usize>()
+                .ok()
+                .filter(|x| *x != 0)
+                .ok_or_else(|| format!("{x}: invalid number of threads"))?,
+            None => sys_info::cpu_num().map_or(1, |x| x.max(1) as usize),
+        };
+
+        let max_count = matches
+            .value_of("max-count")
+            .map(|x| {
+                x.parse::<usize>()
+                    .map_err(|_| format!("{x}: invalid max count"))
+            })
+            .transpose()?;
+
+        Ok(Self {
+            input,
+            recursive: matches.is_present("recursive"),
+            matcher,
+            count: matches.is_present("count"),
+            invert_match: matches.is_present("invert-match"),
+            line_number: matches.is_present("line-number"),
+            byte_offset: matches.is_present("byte-offset"),
+            context,
+            only_matching: matches.is_present("only-matching"),
+            // -a takes precedence like in GNU grep
+            binary_files: if matches.is_present("text") {
+                BinaryFiles::Text
+            } else if matches.is_present("skip-binary") {
+                BinaryFiles::WithoutMatch
+            } else {
+                BinaryFiles::Binary
+            },
+            filter,
+            sort_by_path: matches.value_of("sort") == Some("path"),
+            threads,
+            quiet: matches.is_present("quiet"),
+            files_with_matches: matches.is_present("files-with-matches"),
+            files_without_match: matches.is_present("files-without-match"),
+            max_count,
+            no_messages: matches.is_present("no-messages"),
+            mmap: matches.is_present("mmap"),
+            colors: color.then(|| {
+                std::env::var("GREP_COLORS")
+                    .map(|x| Colors::from_grep_colors(&x))
+                    .unwrap_or_default()
+            }),
+        })
+    }
+}
+
+/// Calls `visit` with every file to search, or the error of a path that couldn't be read, in the
+/// order they're found, until it breaks.
+fn visit_files<'a>(
+    paths: impl Iterator<Item = &'a Path>,
+    recursive: bool,
+    filter: &FileFilter,
+    mut visit: impl FnMut(Result<PathBuf, Box<dyn Error>>) -> ControlFlow<()>,
+) -> ControlFlow<()> {
+    for path in paths {
+        let metadata = match std::fs::metadata(path) {
+            Ok(ok) => ok,
+            Err(err) => {
+                visit(Err(format!("{}: {err}", path.display()).into()))?;
+                continue;
+            }
+        };
+
+        match (metadata.is_dir(), recursive) {
+            (true, false) => visit(Err(format!("{} is a directory", path.display()).into()))?,
+            (false, _) => {
+                if filter.is_file_included(path) {
+                    visit(Ok(path.into()))?;
+                }
+            }
+            (true, true) => {
+                // the directories given as arguments are searched whatever their names
+                let walker = WalkDir::new(path).into_iter().filter_entry(|x| {
+                    x.depth() == 0 || !x.file_type().is_dir() || filter.is_dir_included(x.path())
+                });
+
+                for entry in walker {
+                    let entry = match entry {
+                        Ok(entry) => entry,
+                        Err(err) => {
+                            visit(Err(match err.path() {
+                                Some(path) => format!("{}: {err}", path.display()).into(),
+                                None => format!("{err}").into(),
+                            }))?;
+                            continue;
+                        }
+                    };
+
+                    let metadata = match entry.metadata() {
+                        Ok(ok) => ok,
+                        Err(err) => {
+                            visit(Err(match err.path() {
+                                Some(path) => format!("{}: {err}", path.display()).into(),
+                                None => format!("{err}").into(),
+                            }))?;
+                            continue;
+                        }
+                    };
+
+                    if metadata.is_file() && filter.is_file_included(entry.path()) {
+                        visit(Ok(entry.into_path()))?;
+                    }
+                }
+            }
+        }
+    }
+
+    ControlFlow::Continue(())
+}
+
+fn find_files<'a>(
+    paths: impl Iterator<Item = &'a Path>,
+    recursive: bool,
+    filter: &FileFilter,
+) -> Vec<Result<PathBuf, Box<dyn Error>>> {
+    let mut result = Vec::new();
+    let _ = visit_files(paths, recursive, filter, |x| {
+        result.push(x);
+        ControlFlow::Continue(())
+    });
+    result
+}
+
+/// A line found by the search, borrowed from the input for as long as it's being printed.
+struct LineRef<'a> {
+    kind: HitKind,
+    line_number: usize,
+    byte_offset: usize,
+    line: &'a [u8],
+}
+
+/// Strips the line terminator the same way `BufRead::lines` does.
+fn strip_terminator(line: &[u8]) -> &[u8] {
+    line.strip_suffix(b"\n")
+        .map(|x| x.strip_suffix(b"\r").unwrap_or(x))
+        .unwrap_or(line)
+}
+
+/// Reads the input line by line and calls `on_line` with every line to print as soon as it's
+/// found, keeping only the lines that may become the before context. Returns the number of
+/// selected lines.
+fn search_reader(
+    mut file: impl BufRead,
+    matcher: &dyn Matcher,
+    invert_match: bool,
+    context: Context,
+    max_count: Option<usize>,
+    mut on_line: impl FnMut(LineRef) -> std::io::Result<()>,
+) -> Result<usize, Box<dyn Error>> {
+    let mut count = 0;
+
+    // the lines that may be printed as the before context of the next match, with their line
+    // numbers and byte offsets
+    let mut before = VecDeque::<(usize, usize, Vec<u8>)>::with_capacity(context.before);
+    let mut after_remaining = 0;
+
+    let mut line = Vec::new();
+    let mut line_number = 0;
+    let mut byte_offset = 0;
+
+    loop {
+        // only the context after the last match is left to read once there are enough matches
+        let enough = max_count.is_some_and(|x| count >= x);
+        if enough && after_remaining == 0 {
+            break;
+        }
+
+        line.clear();
+
+        let read = file.read_until(b'\n', &mut line)?;
+        if read == 0 {
+            break;
+        }
+
+        line_number += 1;
+
+        let text = strip_terminator(&line);
+        let line_offset = byte_offset;
+        byte_offset += read;
+
+        if !enough && matcher.is_match(text) != invert_match {
+            count += 1;
+
+            for (line_number, byte_offset, line) in &before {
+                on_line(LineRef {
+                    kind: HitKind::Context,
+                    line_number: *line_number,
+                    byte_offset: *byte_offset,
+                    line,
+                })?;
+            }
+            before.clear();
+
+            on_line(LineRef {
+                kind: HitKind::Match,
+                line_number,
+                byte_offset: line_offset,
+                line: text,
+            })?;
+
+            after_remaining = context.after;
+        } else if after_remaining != 0 {
+            on_line(LineRef {
+                kind: HitKind::Context,
+                line_number,
+                byte_offset: line_offset,
+                line: text,
+            })?;
+
+            after_remaining -= 1;
+        } else if context.before != 0 {
+            // the oldest line's allocation is reused
+            let mut saved = if before.len() == context.before {
+                before.pop_front().map(|x| x.2).unwrap_or_default()
+            } else {
+                Vec::new()
+            };
+
+            saved.clear();
+            saved.extend_from_slice(text);
+            before.push_back((line_number, line_offset, saved));
+        }
+    }
+
+    Ok(count)
+}
+
+/// Searches a whole input at once, like a memory-mapped file. Instead of checking every line, the
+/// matcher jumps to its next candidate and only the line around it is checked, so it only works
+/// for the selected lines themselves, without `--invert-match` or context.
+fn search_buffer(
+    buffer: &[u8],
+    matcher: &dyn Matcher,
+    max_count: Option<usize>,
+    mut on_line: impl FnMut(LineRef) -> std::io::Result<()>,
+) -> std::io::Result<usize> {
+    let mut count = 0;
+
+    // the start of the next line to search and the number of lines before it
+    let mut position = 0;
+    let mut line_number = 0;
+
+    while position < buffer.len() && max_count.is_none_or(|x| count < x) {
+        let Some(candidate) = matcher.find_candidate(&buffer[position..]) else {
+            break;
+        };
+
+        let candidate = position + candidate;
+        let start = memchr::memrchr(b'\n', &buffer[position..candidate])
+            .map_or(position, |x| position + x + 1);
+        if start == buffer.len() {
+            break;
+        }
+
+        let end = memchr::memchr(b'\n', &buffer[start..]).map_or(buffer.len(), |x| start + x + 1);
+
+        line_number += memchr::memchr_iter(b'\n', &buffer[position..start]).count() + 1;
+
+        let text = strip_terminator(&buffer[start..end]);
+        if matcher.is_match(text) {
+            count += 1;
+
+            on_line(LineRef {
+                kind: HitKind::Match,
+                line_number,
+                byte_offset: start,
+                line: text,
+            })?;
+        }
+
+        position = end;
+    }
+
+    Ok(count)
+}
+
+/// Collects the lines found by the search with the spans of their matches.
+pub fn find_lines(
+    file: impl BufRead,
+    matcher: &dyn Matcher,
+    invert_match: bool,
+    context: Context,
+    max_count: Option<usize>,
+) -> Result<Vec<Hit>, Box<dyn Error>> {
+    let mut hits = Vec::new();
+
+    search_reader(file, matcher, invert_match, context, max_count, |line| {
+        hits.push(Hit {
+            kind: line.kind,
+            line_number: line.line_number,
+            byte_offset: line.byte_offset,
+            line: line.line.to_vec(),
+            spans: matcher.find_spans(line.line),
+        });
+
+        Ok(())
+    })?;
+
+    Ok(hits)
+}
+
+/// The length of the start of a buffer that's checked for NUL bytes, the same as the buffer of a
+/// default `BufReader`.
+const BINARY_CHECK_LEN: usize = 8 * 1024;
+
+/// Where the lines of an input come from.
+enum Source<'a, R> {
+    Reader(R),
+    /// The whole input at once, like a memory-mapped file.
+    Buffer(&'a [u8]),
+}
+
+impl<R: BufRead> Source<'_, R> {
+    /// Whether the input looks like a binary file, which GNU grep decides by a NUL byte in the
+    /// first buffer it reads.
+    fn is_binary(&mut self) -> std::io::Result<bool> {
+        Ok(match self {
+            Self::Reader(file) => file.fill_buf()?.contains(&0),
+            Self::Buffer(buffer) => buffer[..buffer.len().min(BINARY_CHECK_LEN)].contains(&0),
+        })
+    }
+
+    fn search(
+        self,
+        matcher: &dyn Matcher,
+        invert_match: bool,
+        context: Context,
+        max_count: Option<usize>,
+        on_line: impl FnMut(LineRef) -> std::io::Result<()>,
+    ) -> Result<usize, Box<dyn Error>> {
+        match self {
+            Self::Reader(file) => {
+                search_reader(file, matcher, invert_match, context, max_count, on_line)
+            }
+            Self::Buffer(buffer)
+                if !invert_match && !context.is_enabled() && matcher.finds_candidates() =>
+            {
+                Ok(search_buffer(buffer, matcher, max_count, on_line)?)
+            }
+            Self::Buffer(buffer) => {
+                search_reader(buffer, matcher, invert_match, context, max_count, on_line)
+            }
+        }
+    }
+}
+
+/// Writes the file name, line number and byte offset that go before a line, as requested.
+fn print_prefix(
+    output: &mut impl Write,
+    filename: Option<&Path>,
+    line_number: Option<usize>,
+    byte_offset: Option<usize>,
+    separator: char,
+    colors: Option<&Colors>,
+) -> std::io::Result<()> {
+    if let Some(filename) = filename {
+        write_colored(output, colors, filename.display(), |x| &x.filename)?;
+        write_colored(output, colors, separator, |x| &x.separator)?;
+    }
+    if let Some(line_number) = line_number {
+        write_colored(output, colors, line_number, |x| &x.line_number)?;
+        write_colored(output, colors, separator, |x| &x.separator)?;
+    }
+    if let Some(byte_offset) = byte_offset {
+        write_colored(output, colors, byte_offset, |x| &x.byte_offset)?;
+        write_colored(output, colors, separator, |x| &x.separator)?;
+    }
+
+    Ok(())
+}
+
+/// Writes the `--` between the groups of lines that aren't adjacent.
+fn print_group_separator(output: &mut impl Write, colors: Option<&Colors>) -> std::io::Result<()> {
+    write_colored(output, colors, "--", |x| &x.separator)?;
+    writeln!(output)
+}
+
+/// Writes the line with every span highlighted.
+fn print_line(
+    output: &mut impl Write,
+    line: &LineRef,
+    spans: &[Range<usize>],
+    colors: Option<&Colors>,
+) -> std::io::Result<()> {
+    let mut position = 0;
+
+    for span in spans.iter().filter(|x| !x.is_empty()) {
+        write_colored_bytes(output, colors, &line.line[position..span.start], |x| {
+            x.line_sgr(line.kind)
+        })?;
+        write_colored_bytes(output, colors, &line.line[span.clone()], |x| {
+            x.match_sgr(line.kind)
+        })?;
+        position = span.end;
+    }
+
+    write_colored_bytes(output, colors, &line.line[position..], |x| {
+        x.line_sgr(line.kind)
+    })?;
+    writeln!(output)
+}
+
+/// Prints a line the way GNU grep does. When the context lines are requested, the groups of lines
+/// that aren't adjacent are separated by `--`.
+fn print_hit(
+    output: &mut impl Write,
+    line: &LineRef,
+    filename: Option<&Path>,
+    config: &Config,
+    previous_line_number: &mut Option<usize>,
+    printed_any: &mut bool,
+) -> std::io::Result<()> {
+    let colors = config.colors.as_ref();
+
+    if config.context.is_enabled() {
+        let adjacent = *previous_line_number == Some(line.line_number - 1);
+
+        if *printed_any && !adjacent {
+            print_group_separator(output, colors)?;
+        }
+    }
+
+    let separator = match line.kind {
+        HitKind::Match => ':',
+        HitKind::Context => '-',
+    };
+
+    // the spans are only needed to highlight or print the matched parts
+    let spans = if colors.is_some() || config.only_matching {
+        config.matcher.find_spans(line.line)
+    } else {
+        Vec::new()
+    };
+
+    if config.only_matching {
+        // every matched part goes on its own line, with the offset of the part itself
+        for span in spans.iter().filter(|x| !x.is_empty()) {
+            print_prefix(
+                output,
+                filename,
+                config.line_number.then_some(line.line_number),
+                config.byte_offset.then_some(line.byte_offset + span.start),
+                separator,
+                colors,
+            )?;
+            write_colored_bytes(output, colors, &line.line[span.clone()], |x| {
+                &x.selected_match
+            })?;
+            writeln!(output)?;
+        }
+    } else {
+        print_prefix(
+            output,
+            filename,
+            config.line_number.then_some(line.line_number),
+            config.byte_offset.then_some(line.byte_offset),
+            separator,
+            colors,
+        )?;
+        print_line(output, line, &spans, colors)?;
+    }
+
+    *previous_line_number = Some(line.line_number);
+    *printed_any = true;
+
+    Ok(())
+}
+
+fn print_count(
+    output: &mut impl Write,
+    count: usize,
+    filename: Option<&Path>,
+    colors: Option<&Colors>,
+) -> std::io::Result<()> {
+    print_prefix(output, filename, None, None, ':', colors)?;
+    writeln!(output, "{count}")
+}
+
+/// Searches the input and prints what's found the way the config asks for, as soon as it's found.
+/// Returns whether a line was selected.
+fn search_and_print<R: BufRead>(
+    output: &mut impl Write,
+    mut source: Source<R>,
+    name: &Path,
+    print_header: bool,
+    config: &Config,
+    printed_any: &mut bool,
+) -> Result<bool, Box<dyn Error>> {
+    let colors = config.colors.as_ref();
+    let filename = print_header.then_some(name);
+    let matcher = config.matcher.as_ref();
+
+    let binary = config.binary_files != BinaryFiles::Text && source.is_binary()?;
+    if binary && config.binary_files == BinaryFiles::WithoutMatch {
+        return Ok(false);
+    }
+
+    let lists_files = config.quiet || config.files_with_matches || config.files_without_match;
+
+    if lists_files || config.count || binary {
+        // the lines are only counted, and a single one is enough to know that a file matches
+        let max_count = if config.count && !lists_files {
+            config.max_count
+        } else {
+            Some(config.max_count.unwrap_or(1).min(1))
+        };
+
+        let count = source.search(
+            matcher,
+            config.invert_match,
+            Context::default(),
+            max_count,
+            |_| Ok(()),
+        )?;
+
+        if config.quiet {
+            // nothing's printed
+        } else if lists_files {
+            if (count != 0) != config.files_without_match {
+                write_colored(output, colors, name.display(), |x| &x.filename)?;
+                writeln!(output)?;
+            }
+        } else if config.count {
+            print_count(output, count, filename, colors)?;
+        } else if count != 0 {
+            // the lines of a binary file are likely garbage
+            writeln!(output, "Binary file {} matches", name.display())?;
+        }
+
+        return Ok(count != 0);
+    }
+
+    let mut previous_line_number = None;
+    let count = source.search(
+        matcher,
+        config.invert_match,
+        config.context,
+        config.max_count,
+        |line| {
+            print_hit(
+                output,
+                &line,
+                filename,
+                config,
+                &mut previous_line_number,
+                printed_any,
+            )
+        },
+    )?;
+
+    Ok(count != 0)
+}
+
+/// Opens and searches the file, through a memory map with `--mmap`.
+fn search_file(
+    output: &mut impl Write,
+    path: &Path,
+    print_header: bool,
+    config: &Config,
+    printed_any: &mut bool,
+) -> Result<bool, Box<dyn Error>> {
+    let file = std::fs::File::open(path)?;
+
+    // the empty files can't be mapped, and neither can the special ones, which are read instead
+    if config.mmap && file.metadata()?.len() != 0 {
+        // SAFETY: the map is only read, but the file mustn't be truncated during the search or
+        // the process crashes, which is why the memory maps are opt-in
+        if let Ok(map) = unsafe { memmap2::Mmap::map(&file) } {
+            let source = Source::<BufReader<std::fs::File>>::Buffer(&map);
+            return search_and_print(output, source, path, print_header, config, printed_any);
+        }
+    }
+
+    let source = Source::Reader(BufReader::new(file));
+    search_and_print(output, source, path, print_header, config, printed_any)
+}
 
-        let pattern = matches.value_of("pattern").expect("it's required argument");
-        let regex = RegexBuilder::new(pattern)
-            .case_insensitive(matches.is_present("insensitive"))
-            .build()
-            .map_err(|_| format!("Invalid pattern \"{pattern}\""))?;
+/// Calls `visit` with every file to search like [`visit_files`], in the order of their paths with
+/// `--sort=path`.
+fn for_each_file(
+    files: &[PathBuf],
+    config: &Config,
+    mut visit: impl FnMut(Result<PathBuf, Box<dyn Error>>) -> ControlFlow<()>,
+) -> ControlFlow<()> {
+    let paths = files.iter().map(|x| x.as_path());
+
+    if !config.sort_by_path {
+        return visit_files(paths, config.recursive, &config.filter, visit);
+    }
 
-        let files = matches
-            .values_of_lossy("file")
-            .expect("it's required argument");
+    // nothing can be searched before all the paths are known
+    let mut entries = find_files(paths, config.recursive, &config.filter);
 
-        let input = if let (Some("-"), 1) = (files.get(0).map(AsRef::as_ref), files.len()) {
-            Input::Stdin
+    entries.sort_by(|a, b| match (a, b) {
+        (Ok(a), Ok(b)) => a.cmp(b),
+        (Ok(_), Err(_)) => std::cmp::Ordering::Greater,
+        (Err(_), Ok(_)) => std::cmp::Ordering::Less,
+        (Err(_), Err(_)) => std::cmp::Ordering::Equal,
+    });
+    entries.into_iter().try_for_each(&mut visit)
+}
+
+/// What a worker printed for a file, held until the files before it are written.
+struct Searched {
+    output: Vec<u8>,
+    /// Whether the start of the output was written already, when it was the file's turn.
+    started: bool,
+    printed_any: bool,
+    result: Result<bool, String>,
+}
+
+/// A path found by the recursive search, in the order it's printed.
+enum Found {
+    Error(String),
+    File(PathBuf, Searched),
+}
+
+/// The outcome of a search, which decides the exit code like in GNU grep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Status {
+    /// A line was selected, even with `-L` which lists the other files.
+    Selected,
+    NotSelected,
+    /// Something couldn't be read or written, unless `-q` found a match anyway.
+    Error,
+}
+
+impl Status {
+    pub fn exit_code(self) -> u8 {
+        match self {
+            Self::Selected => 0,
+            Self::NotSelected => 1,
+            Self::Error => 2,
+        }
+    }
+}
+
+/// The output of the whole search and what's known about it so far.
+struct Printer<W> {
+    output: W,
+    printed_any: bool,
+    selected_any: bool,
+    error: bool,
+}
+
+impl<W: Write> Printer<W> {
+    fn new(output: W) -> Self {
+        Self {
+            output,
+            printed_any: false,
+            selected_any: false,
+            error: false,
+        }
+    }
+
+    /// Prints the error about a file unless `-s` is given.
+    fn report(&mut self, err: impl Display, config: &Config) {
+        self.error = true;
+
+        if !config.no_messages {
+            eprintln!("{err}");
+        }
+    }
+
+    /// Takes the outcome of searching a file into account, and tells whether to go on.
+    fn record(
+        &mut self,
+        path: &Path,
+        result: Result<bool, impl Display>,
+        config: &Config,
+    ) -> ControlFlow<()> {
+        match result {
+            Ok(selected) => self.selected_any |= selected,
+            Err(err) => self.report(format_args!("{}: {err}", path.display()), config),
+        }
+
+        // there's nothing more to know with -q after the first match
+        if config.quiet && self.selected_any {
+            ControlFlow::Break(())
         } else {
-            Input::Files(files.into_iter().map(PathBuf::from).collect())
+            ControlFlow::Continue(())
+        }
+    }
+
+    /// Writes what a worker printed for a file.
+    fn write_searched(
+        &mut self,
+        path: &Path,
+        searched: Searched,
+        config: &Config,
+    ) -> ControlFlow<()> {
+        // the worker couldn't know whether the groups of lines before it were printed
+        let separated = if config.context.is_enabled()
+            && self.printed_any
+            && searched.printed_any
+            && !searched.started
+        {
+            print_group_separator(&mut self.output, config.colors.as_ref())
+        } else {
+            Ok(())
         };
 
-        Ok(Self {
-            input,
-            recursive: matches.is_present("recursive"),
-            pattern: regex,
-            count: matches.is_present("count"),
-            invert_match: matches.is_present("invert-match"),
-        })
+        if let Err(err) = separated.and_then(|_| self.output.write_all(&searched.output)) {
+            eprintln!("{err}");
+            self.error = true;
+        }
+
+        self.printed_any |= searched.printed_any;
+        self.record(path, searched.result, config)
+    }
+
+    fn finish(mut self, config: &Config) -> Status {
+        if let Err(err) = self.output.flush() {
+            eprintln!("{err}");
+            self.error = true;
+        }
+
+        match (self.selected_any, self.error) {
+            (true, _) if config.quiet => Status::Selected,
+            (_, true) => Status::Error,
+            (true, false) => Status::Selected,
+            (false, false) => Status::NotSelected,
+        }
     }
 }
 
-fn find_files<'a>(
-    paths: impl Iterator<Item = &'a Path>,
-    recursive: bool,
-) -> Vec<Result<PathBuf, Box<dyn Error>>> {
-    let mut result = Vec::new();
+/// Searches the files one after the other, straight to the output.
+fn search_files_serially(files: &[PathBuf], config: &Config, printer: &mut Printer<impl Write>) {
+    let print_header = files.len() != 1 || config.recursive;
 
-    for path in paths {
-        let metadata = match std::fs::metadata(path) {
-            Ok(ok) => ok,
-            Err(err) => {
-                result.push(Err(format!("{}: {err}", path.display()).into()));
-                continue;
-            }
-        };
+    let _ = for_each_file(files, config, |entry| match entry {
+        Ok(path) => {
+            let result = search_file(
+                &mut printer.output,
+                &path,
+                print_header,
+                config,
+                &mut printer.printed_any,
+            );
 
-        match (metadata.is_dir(), recursive) {
-            (true, false) => result.push(Err(format!("{} is a directory", path.display()).into())),
-            (false, _) => result.push(Ok(path.into())),
-            (true, true) => {
-                let walker = WalkDir::new(path).into_iter();
+            printer.record(&path, result, config)
+        }
+        Err(err) => {
+            printer.report(err, config);
+            ControlFlow::Continue(())
+        }
+    });
+}
 
-                for entry in walker.into_iter() {
-                    let entry = match entry {
-                        Ok(entry) => entry,
-                        Err(err) => {
-                            result.push(Err(match err.path() {
-                                Some(path) => format!("{}: {err}", path.display()).into(),
-                                None => format!("{err}").into(),
-                            }));
-                            continue;
-                        }
-                    };
+/// The most that a worker prints for a file ahead of its turn, after which it waits to write it.
+const BUFFERED_OUTPUT_LEN: usize = 1024 * 1024;
 
-                    let metadata = match entry.metadata() {
-                        Ok(ok) => ok,
-                        Err(err) => {
-                            result.push(Err(match err.path() {
-                                Some(path) => format!("{}: {err}", path.display()).into(),
-                                None => format!("{err}").into(),
-                            }));
-                            continue;
-                        }
-                    };
+/// How much a worker prints for a file before it writes it to the output in its turn.
+const OUTPUT_CHUNK_LEN: usize = 64 * 1024;
 
-                    if metadata.is_file() {
-                        result.push(Ok(entry.into_path()));
-                    }
-                }
+/// The output of the workers, which is written in the order of the files.
+struct Turns<'a, W> {
+    printer: &'a mut Printer<W>,
+    /// The index of the file or error to write next.
+    next: usize,
+    /// What was found after `next`, held until its turn.
+    pending: BTreeMap<usize, Found>,
+    /// Whether `-q` found a match or the output is gone, so nothing's searched anymore.
+    stopped: bool,
+}
+
+impl<W: Write> Turns<'_, W> {
+    fn write_found(&mut self, found: Found, config: &Config) -> ControlFlow<()> {
+        match found {
+            Found::Error(err) => {
+                self.printer.report(err, config);
+                ControlFlow::Continue(())
             }
+            Found::File(path, searched) => self.printer.write_searched(&path, searched, config),
         }
     }
 
-    result
+    /// Moves on to the next file, writing what's already found after it.
+    fn advance(&mut self, flow: ControlFlow<()>, config: &Config) {
+        if flow.is_break() {
+            self.stopped = true;
+            return;
+        }
+
+        self.next += 1;
+
+        while let Some(found) = self.pending.remove(&self.next) {
+            if self.write_found(found, config).is_break() {
+                self.stopped = true;
+                return;
+            }
+            self.next += 1;
+        }
+    }
 }
 
-fn find_lines(
-    file: impl BufRead,
-    pattern: &Regex,
-    invert_match: bool,
-) -> Result<Vec<String>, Box<dyn Error>> {
-    let mut matches = Vec::new();
+/// The turns shared by the walker and the workers.
+struct Ordered<'a, W> {
+    turns: Mutex<Turns<'a, W>>,
+    /// Notified whenever `next` moves on or the search stops.
+    changed: Condvar,
+    /// The most entries in `pending`.
+    pending_len: usize,
+}
+
+impl<'a, W: Write> Ordered<'a, W> {
+    fn lock(&self) -> MutexGuard<'_, Turns<'a, W>> {
+        self.turns.lock().expect("no worker panics")
+    }
+
+    /// Writes what was found if it's its turn, or else holds it once there's room for it.
+    fn deposit(&self, index: usize, found: Found, config: &Config) {
+        let mut turns = self.lock();
 
-    for line in file.lines() {
-        let line = line?;
-        let line_match = pattern.is_match(&line);
+        while !turns.stopped && index != turns.next && turns.pending.len() >= self.pending_len {
+            turns = self.changed.wait(turns).expect("no worker panics");
+        }
+
+        if turns.stopped {
+            return;
+        }
 
-        if line_match != invert_match {
-            matches.push(line);
+        if index == turns.next {
+            let flow = turns.write_found(found, config);
+            turns.advance(flow, config);
+            self.changed.notify_all();
+        } else {
+            turns.pending.insert(index, found);
         }
     }
+}
 
-    Ok(matches)
+/// Where a worker prints a file: to a buffer, which is written to the output a chunk at a time
+/// once it's the file's turn. Ahead of its turn, the worker waits when the buffer is full. The
+/// output is only locked to write a chunk, never while searching.
+struct Deferred<'o, 'a, W> {
+    ordered: &'o Ordered<'a, W>,
+    index: usize,
+    context: bool,
+    colors: Option<&'o Colors>,
+    buffer: Vec<u8>,
+    /// The length of the buffer at which it's written if it's the file's turn.
+    write_at: usize,
+    /// Whether the start of the buffer was written already.
+    started: bool,
 }
 
-pub fn run(config: Config) {
-    match config.input {
-        Input::Stdin => {
-            let lines = find_lines(
-                BufReader::new(std::io::stdin()),
-                &config.pattern,
-                config.invert_match,
-            );
+impl<W: Write> Deferred<'_, '_, W> {
+    /// Writes the buffer to the output if it's the file's turn, or else waits for it if `wait`.
+    fn write_in_turn(&mut self, wait: bool) -> std::io::Result<()> {
+        let mut turns = self.ordered.lock();
+        while wait && !turns.stopped && turns.next != self.index {
+            turns = self.ordered.changed.wait(turns).expect("no worker panics");
+        }
 
-            match lines {
-                Ok(lines) => {
-                    if config.count {
-                        println!("{}", lines.len());
-                    } else {
-                        for line in lines {
-                            println!("{}", line);
-                        }
-                    }
-                }
-                Err(err) => eprintln!("{err}"),
-            }
+        if turns.stopped {
+            return Err(std::io::Error::other("the search has stopped"));
+        }
+
+        if turns.next != self.index {
+            self.write_at += OUTPUT_CHUNK_LEN;
+            return Ok(());
+        }
+
+        // only the lines print that much, so the file has printed some
+        if !self.started && self.context && turns.printer.printed_any {
+            print_group_separator(&mut turns.printer.output, self.colors)?;
+        }
+        turns.printer.output.write_all(&self.buffer)?;
+
+        self.buffer.clear();
+        self.write_at = OUTPUT_CHUNK_LEN;
+        self.started = true;
+
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for Deferred<'_, '_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        if self.buffer.len() >= BUFFERED_OUTPUT_LEN {
+            self.write_in_turn(true)?;
+        } else if self.buffer.len() >= self.write_at {
+            self.write_in_turn(false)?;
         }
-        Input::Files(files) => {
-            let print_header = files.len() != 1 || config.recursive;
-            let entries = find_files(files.iter().map(|x| x.as_path()), config.recursive);
 
-            for entry in entries {
-                let entry = match entry {
-                    Ok(ok) => ok,
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Searches a file for a worker, and hands what's left of its output over to be written in turn.
+fn search_in_turn<W: Write>(
+    ordered: &Ordered<'_, W>,
+    index: usize,
+    path: PathBuf,
+    print_header: bool,
+    config: &Config,
+) {
+    if ordered.lock().stopped {
+        return;
+    }
+
+    let mut output = Deferred {
+        ordered,
+        index,
+        context: config.context.is_enabled(),
+        colors: config.colors.as_ref(),
+        buffer: Vec::new(),
+        write_at: OUTPUT_CHUNK_LEN,
+        started: false,
+    };
+    let mut printed_any = false;
+    let result = search_file(&mut output, &path, print_header, config, &mut printed_any);
+
+    let searched = Searched {
+        output: output.buffer,
+        started: output.started,
+        printed_any,
+        result: result.map_err(|err| err.to_string()),
+    };
+    ordered.deposit(index, Found::File(path, searched), config);
+}
+
+/// Searches the files on `config.threads` threads while the directories are walked. The workers
+/// print to buffers that are written once the files before them are, the file to write next as
+/// it's searched, so the output is the same as a search on a single thread. The buffers, the
+/// files waiting for their turn and the paths waiting for a worker are all bounded.
+fn search_files<W: Write + Send>(files: &[PathBuf], config: &Config, printer: &mut Printer<W>) {
+    // a single file has nothing to search at the same time
+    if config.threads == 1 || (files.len() == 1 && !config.recursive) {
+        search_files_serially(files, config, printer);
+        return;
+    }
+
+    let print_header = files.len() != 1 || config.recursive;
+
+    let (job_sender, job_receiver) = mpsc::sync_channel::<(usize, PathBuf)>(config.threads);
+
+    // dropped with the last worker, which stops the walk
+    let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+    let ordered = Ordered {
+        turns: Mutex::new(Turns {
+            printer,
+            next: 0,
+            pending: BTreeMap::new(),
+            stopped: false,
+        }),
+        changed: Condvar::new(),
+        pending_len: config.threads * 2,
+    };
+    let ordered = &ordered;
+
+    std::thread::scope(|scope| {
+        scope.spawn(move || {
+            let mut index = 0;
+
+            let _ = for_each_file(files, config, |entry| {
+                // the receivers only hang up when the search has stopped
+                let sent = match entry {
+                    Ok(path) => job_sender.send((index, path)).is_ok(),
                     Err(err) => {
-                        eprintln!("{err}");
-                        continue;
+                        ordered.deposit(index, Found::Error(err.to_string()), config);
+                        !ordered.lock().stopped
                     }
                 };
+                index += 1;
 
-                let file = match std::fs::File::open(&entry) {
-                    Ok(file) => file,
-                    Err(err) => {
-                        eprintln!("{}: {err}", entry.display());
-                        continue;
-                    }
+                if sent {
+                    ControlFlow::Continue(())
+                } else {
+                    ControlFlow::Break(())
+                }
+            });
+        });
+
+        for _ in 0..config.threads {
+            let job_receiver = job_receiver.clone();
+
+            scope.spawn(move || loop {
+                let job = job_receiver.lock().expect("no worker panics").recv();
+                let Ok((index, path)) = job else {
+                    break;
                 };
 
-                let lines = find_lines(BufReader::new(file), &config.pattern, config.invert_match);
-
-                match lines {
-                    Ok(lines) => {
-                        if config.count {
-                            if print_header {
-                                print!("{}:", entry.display());
-                            }
-                            println!("{}", lines.len());
-                        } else {
-                            for line in lines {
-                                if print_header {
-                                    print!("{}:", entry.display());
-                                }
-
-                                println!("{}", line);
-                            }
-                        }
-                    }
-                    Err(err) => eprintln!("{}: {err}", entry.display()),
+                search_in_turn(ordered, index, path, print_header, config);
+
+                if ordered.lock().stopped {
+                    break;
                 }
-            }
+            });
+        }
+
+        drop(job_receiver);
+    });
+}
+
+pub fn run(config: Config) -> Status {
+    // not locked, so the workers can write to it
+    let mut printer = Printer::new(BufWriter::new(std::io::stdout()));
+
+    match &config.input {
+        Input::Stdin => {
+            let result = search_and_print(
+                &mut printer.output,
+                Source::Reader(BufReader::new(std::io::stdin())),
+                Path::new("(standard input)"),
+                false,
+                &config,
+                &mut printer.printed_any,
+            );
+
+            let _ = printer.record(Path::new("(standard input)"), result, &config);
         }
+        Input::Files(files) => search_files(files, &config, &mut printer),
     }
+
+    printer.finish(&config)
 }
 
 #[cfg(test)]
+#[allow(clippy::single_range_in_vec_init)]
 mod tests {
     use std::{
         io::Cursor,
         path::{Path, PathBuf},
     };
 
-    use super::find_files;
+    use super::{
+        build_glob_set, find_files, new_matcher, Colors, Context, FileFilter, Hit, HitKind,
+        MatchMode, Source,
+    };
     use rand::{distributions::Alphanumeric, Rng};
-    use regex::{Regex, RegexBuilder};
+    use regex::bytes::{Regex, RegexBuilder};
 
     #[test]
     fn test_find_files() {
         // Verify that the function finds a file known to exist
-        let files = super::find_files([Path::new("./tests/inputs/fox.txt")].into_iter(), false);
+        let files = super::find_files(
+            [Path::new("./tests/inputs/fox.txt")].into_iter(),
+            false,
+            &FileFilter::default(),
+        );
         assert_eq!(files.len(), 1);
         assert_eq!(
             files[0].as_ref().unwrap(),
@@ -261,13 +2050,21 @@ mod tests {
         );
         // The function should reject a directory
         // without the recursive option
-        let files = find_files([Path::new("./tests/inputs")].into_iter(), false);
+        let files = find_files(
+            [Path::new("./tests/inputs")].into_iter(),
+            false,
+            &FileFilter::default(),
+        );
         assert_eq!(files.len(), 1);
         if let Err(e) = &files[0] {
             assert_eq!(e.to_string(), "./tests/inputs is a directory");
         }
         // Verify the function recurses to find four files in the directory
-        let res = find_files([Path::new("./tests/inputs")].into_iter(), true);
+        let res = find_files(
+            [Path::new("./tests/inputs")].into_iter(),
+            true,
+            &FileFilter::default(),
+        );
         let mut files: Vec<String> = res
             .iter()
             .map(|r| r.as_ref().unwrap().to_str().unwrap().replace('\\', "/"))
@@ -290,21 +2087,55 @@ mod tests {
             .map(char::from)
             .collect();
         // Verify that the function returns the bad file as an error
-        let files = find_files([PathBuf::from(bad)].iter().map(|x| x.as_path()), false);
+        let files = find_files(
+            [PathBuf::from(bad)].iter().map(|x| x.as_path()),
+            false,
+            &FileFilter::default(),
+        );
         assert_eq!(files.len(), 1);
         assert!(files[0].is_err());
     }
 
+    #[test]
+    fn test_find_files_filter() {
+        let find = |include: &[&str], exclude: &[&str], exclude_dir: &[&str]| {
+            let filter = FileFilter {
+                include: build_glob_set(include.iter().copied()).unwrap(),
+                exclude: build_glob_set(exclude.iter().copied()).unwrap(),
+                exclude_dir: build_glob_set(exclude_dir.iter().copied()).unwrap(),
+            };
+            let mut files = find_files([Path::new("./tests")].into_iter(), true, &filter)
+                .into_iter()
+                .map(|x| x.unwrap().to_str().unwrap().replace('\\', "/"))
+                .collect::<Vec<_>>();
+            files.sort();
+            files
+        };
+
+        assert_eq!(
+            find(&["*.txt"], &["[be]*"], &["expected"]),
+            vec![
+                "./tests/binary/latin1.txt",
+                "./tests/inputs/fox.txt",
+                "./tests/inputs/nobody.txt",
+                "./tests/patterns.txt",
+            ]
+        );
+        // The directories given as arguments are searched anyway
+        assert_eq!(find(&["*.txt"], &[], &["tests", "in*", "exp*"]).len(), 2);
+        assert_eq!(find(&["*.bin"], &[], &[]), vec!["./tests/binary/image.bin"]);
+    }
+
     #[test]
     fn test_find_lines() {
         let text = b"Lorem\nIpsum\r\nDOLOR";
         // The pattern _or_ should match the one line, "Lorem"
         let re1 = Regex::new("or").unwrap();
-        let matches = super::find_lines(Cursor::new(&text), &re1, false);
+        let matches = super::find_lines(Cursor::new(&text), &re1, false, Context::default(), None);
         assert!(matches.is_ok());
         assert_eq!(matches.unwrap().len(), 1);
         // When inverted, the function should match the other two lines
-        let matches = super::find_lines(Cursor::new(&text), &re1, true);
+        let matches = super::find_lines(Cursor::new(&text), &re1, true, Context::default(), None);
         assert!(matches.is_ok());
         assert_eq!(matches.unwrap().len(), 2);
         // This regex will be case-insensitive
@@ -313,12 +2144,292 @@ mod tests {
             .build()
             .unwrap();
         // The two lines "Lorem" and "DOLOR" should match
-        let matches = super::find_lines(Cursor::new(&text), &re2, false);
+        let matches = super::find_lines(Cursor::new(&text), &re2, false, Context::default(), None);
         assert!(matches.is_ok());
         assert_eq!(matches.unwrap().len(), 2);
         // When inverted, the one remaining line should match
-        let matches = super::find_lines(Cursor::new(&text), &re2, true);
+        let matches = super::find_lines(Cursor::new(&text), &re2, true, Context::default(), None);
         assert!(matches.is_ok());
         assert_eq!(matches.unwrap().len(), 1);
     }
+
+    #[test]
+    fn test_find_lines_hits() {
+        let text = b"Lorem\nIpsum\r\nDOLOR or";
+        let re = Regex::new("or").unwrap();
+
+        let hits =
+            super::find_lines(Cursor::new(&text), &re, false, Context::default(), None).unwrap();
+        assert_eq!(
+            hits,
+            vec![
+                Hit {
+                    kind: HitKind::Match,
+                    line_number: 1,
+                    byte_offset: 0,
+                    line: b"Lorem".to_vec(),
+                    spans: vec![1..3],
+                },
+                Hit {
+                    kind: HitKind::Match,
+                    line_number: 3,
+                    byte_offset: 13,
+                    line: b"DOLOR or".to_vec(),
+                    spans: vec![6..8],
+                },
+            ]
+        );
+
+        // The inverted matches don't match anything
+        let hits =
+            super::find_lines(Cursor::new(&text), &re, true, Context::default(), None).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].line_number, 2);
+        assert_eq!(hits[0].byte_offset, 6);
+        assert!(hits[0].spans.is_empty());
+
+        // The reading stops at the max count, but the context after the last match is kept
+        let hits =
+            super::find_lines(Cursor::new(&text), &re, false, Context::default(), Some(1)).unwrap();
+        assert_eq!(hits.len(), 1);
+        let context = Context {
+            before: 0,
+            after: 5,
+            given: true,
+        };
+        let hits = super::find_lines(Cursor::new(&text), &re, false, context, Some(1)).unwrap();
+        assert_eq!(
+            hits.iter().map(|x| x.kind).collect::<Vec<_>>(),
+            vec![HitKind::Match, HitKind::Context, HitKind::Context]
+        );
+        let hits = super::find_lines(Cursor::new(&text), &re, false, context, Some(0)).unwrap();
+        assert!(hits.is_empty());
+    }
+
+    #[test]
+    fn test_find_lines_context() {
+        let text = b"1\n2\nmatch\n4\n5\n6\n7\nmatch\n9\nmatch\n11\n12";
+        let re = Regex::new("match").unwrap();
+        let find = |before, after| {
+            super::find_lines(
+                Cursor::new(&text),
+                &re,
+                false,
+                Context {
+                    before,
+                    after,
+                    given: true,
+                },
+                None,
+            )
+            .unwrap()
+            .iter()
+            .map(|x| (x.line_number, x.kind))
+            .collect::<Vec<_>>()
+        };
+
+        use HitKind::{Context as C, Match as M};
+
+        assert_eq!(find(0, 0), vec![(3, M), (8, M), (10, M)]);
+        assert_eq!(
+            find(1, 1),
+            vec![
+                (2, C),
+                (3, M),
+                (4, C),
+                (7, C),
+                (8, M),
+                (9, C),
+                (10, M),
+                (11, C)
+            ]
+        );
+        // The overlapping windows are merged
+        assert_eq!(
+            find(3, 0),
+            vec![
+                (1, C),
+                (2, C),
+                (3, M),
+                (5, C),
+                (6, C),
+                (7, C),
+                (8, M),
+                (9, C),
+                (10, M)
+            ]
+        );
+        assert_eq!(
+            find(0, 2),
+            vec![
+                (3, M),
+                (4, C),
+                (5, C),
+                (8, M),
+                (9, C),
+                (10, M),
+                (11, C),
+                (12, C)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_search_buffer() {
+        let text = b"the fox\r\n\nno match here\nA FOX AT THE END\r\nfoxfox\nlast fox";
+        let find_buffer = |matcher: &dyn super::Matcher, max_count| {
+            let mut lines = Vec::new();
+            super::search_buffer(text, matcher, max_count, |x| {
+                lines.push((x.line_number, x.byte_offset, x.line.to_vec()));
+                Ok(())
+            })
+            .unwrap();
+            lines
+        };
+        let find_reader = |matcher: &dyn super::Matcher, max_count| {
+            super::find_lines(
+                Cursor::new(text),
+                matcher,
+                false,
+                Context::default(),
+                max_count,
+            )
+            .unwrap()
+            .into_iter()
+            .map(|x| (x.line_number, x.byte_offset, x.line))
+            .collect::<Vec<_>>()
+        };
+
+        // Jumping between the candidates finds the same lines as reading every line
+        let cases: &[(&[&str], bool, bool, MatchMode)] = &[
+            (&["fox"], false, false, MatchMode::Substring),
+            (&["fox"], true, true, MatchMode::Word),
+            (&["fox$", "^$"], false, false, MatchMode::Substring),
+            (&["[^a]o", "e\\s"], false, true, MatchMode::Substring),
+            (&["foxfox", "the fox"], true, false, MatchMode::Line),
+            (&[""], false, false, MatchMode::Substring),
+            (&[], false, false, MatchMode::Substring),
+        ];
+
+        for (patterns, fixed, insensitive, mode) in cases {
+            let matcher = new_matcher(patterns, *fixed, *insensitive, *mode).unwrap();
+            for max_count in [None, Some(1)] {
+                assert_eq!(
+                    find_buffer(matcher.as_ref(), max_count),
+                    find_reader(matcher.as_ref(), max_count),
+                    "{patterns:?}"
+                );
+            }
+        }
+        assert_eq!(find_buffer(&Regex::new("(?i)fox").unwrap(), None).len(), 4);
+
+        // the start and end of the whole buffer aren't the ones of its lines
+        for patterns in [&[r"\Afox"][..], &["fox\\z"], &["x", "(?-m)^A"], &[r"\\A"]] {
+            let matcher = new_matcher(patterns, false, false, MatchMode::Substring).unwrap();
+            let mut lines = Vec::new();
+            Source::<Cursor<&[u8]>>::Buffer(text)
+                .search(matcher.as_ref(), false, Context::default(), None, |x| {
+                    lines.push((x.line_number, x.byte_offset, x.line.to_vec()));
+                    Ok(())
+                })
+                .unwrap();
+
+            assert_eq!(lines, find_reader(matcher.as_ref(), None), "{patterns:?}");
+        }
+
+        assert!(super::has_text_anchors(r"^\Afoo"));
+        assert!(super::has_text_anchors(r"(?i-m:foo)$"));
+        assert!(!super::has_text_anchors(r"\\A(?i)z"));
+    }
+
+    #[test]
+    fn test_colors_from_grep_colors() {
+        assert_eq!(Colors::from_grep_colors(""), Colors::default());
+
+        let colors = Colors::from_grep_colors("ms=04;32:fn=:ne:xx=1");
+        assert_eq!(colors.selected_match, "04;32");
+        assert_eq!(colors.context_match, "01;31");
+        assert_eq!(colors.filename, "");
+        assert!(!colors.erase_line);
+
+        let colors = Colors::from_grep_colors("mt=7:sl=1:cx=2");
+        assert_eq!(colors.selected_match, "7");
+        assert_eq!(colors.context_match, "7");
+        assert_eq!(colors.selected_line, "1");
+        assert_eq!(colors.context_line, "2");
+    }
+
+    #[test]
+    fn test_matchers() {
+        let find = |patterns: &[&str], fixed, insensitive, mode, line: &str| {
+            new_matcher(patterns, fixed, insensitive, mode)
+                .unwrap()
+                .find_spans(line.as_bytes())
+        };
+
+        // Any of the patterns match, the longest one wins for the fixed strings
+        assert_eq!(
+            find(
+                &["the", "they"],
+                true,
+                false,
+                MatchMode::Substring,
+                "they the"
+            ),
+            vec![0..4, 5..8]
+        );
+        assert_eq!(
+            find(&["a.", "x"], false, false, MatchMode::Substring, "ab x"),
+            vec![0..2, 3..4]
+        );
+        assert!(find(&["a."], true, false, MatchMode::Substring, "ab").is_empty());
+
+        // The words can't be next to a word character, but other characters are fine
+        assert_eq!(
+            find(&["foo"], false, false, MatchMode::Word, "foobar foo_ foo"),
+            vec![12..15]
+        );
+        assert_eq!(
+            find(&["-x"], true, false, MatchMode::Word, "a-x -x"),
+            vec![4..6]
+        );
+        // a shorter alternative that isn't a word doesn't hide the longer one that is
+        assert_eq!(
+            find(&["ab|abc"], false, false, MatchMode::Word, "abc"),
+            vec![0..3]
+        );
+        assert_eq!(
+            find(&["foo"], false, false, MatchMode::Word, "foo foo,foo"),
+            vec![0..3, 4..7, 8..11]
+        );
+        assert_eq!(
+            find(&["[a-z]*"], false, false, MatchMode::Word, "a! b"),
+            vec![0..1, 2..2, 3..4]
+        );
+
+        assert_eq!(
+            find(&["b", "ab"], true, false, MatchMode::Line, "ab"),
+            vec![0..2]
+        );
+        assert!(find(&["a"], false, false, MatchMode::Line, "ab").is_empty());
+
+        // The non-ASCII fixed strings are folded too
+        assert_eq!(
+            find(&["été"], true, true, MatchMode::Substring, "ÉTÉ"),
+            vec![0..5]
+        );
+        assert_eq!(
+            find(&["THE"], true, true, MatchMode::Word, "the"),
+            vec![0..3]
+        );
+
+        // No patterns match nothing
+        let no_patterns: &[&str] = &[];
+        assert!(
+            !new_matcher(no_patterns, false, false, MatchMode::Substring)
+                .unwrap()
+                .is_match(b"anything")
+        );
+        assert!(new_matcher(&["a)|(b"], false, false, MatchMode::Substring).is_err());
+    }
 }
@@ -5,11 +5,9 @@ fn main() -> ExitCode {
         Ok(ok) => ok,
         Err(err) => {
             eprintln!("{err}");
-            return ExitCode::FAILURE;
+            return ExitCode::from(2);
         }
     };
 
-    grepr::run(ok);
-
-    ExitCode::SUCCESS
+    ExitCode::from(grepr::run(ok).exit_code())
 }
@@ -1,3 +1,6 @@
+// the tests pass their arguments as `&[..]`, which newer versions of clippy flag
+#![allow(clippy::needless_borrows_for_generic_args)]
+
 use assert_cmd::Command;
 use predicates::prelude::*;
 use rand::{distributions::Alphanumeric, Rng};
@@ -12,6 +15,9 @@ const EMPTY: &str = "tests/inputs/empty.txt";
 const FOX: &str = "tests/inputs/fox.txt";
 const NOBODY: &str = "tests/inputs/nobody.txt";
 const INPUTS_DIR: &str = "tests/inputs";
+const PATTERNS: &str = "tests/patterns.txt";
+const IMAGE: &str = "tests/binary/image.bin";
+const LATIN1: &str = "tests/binary/latin1.txt";
 
 //============================================================================
 fn gen_bad_file() -> String {
@@ -277,3 +283,370 @@ fn stdin_insensitive_count() -> TestResult {
         .stdout(expected);
     Ok(())
 }
+
+//============================================================================
+#[test]
+fn context_multiple_files() -> TestResult {
+    run(
+        &["-n", "-C1", "-i", "the", BUSTLE, NOBODY],
+        "tests/expected/bustle.nobody.the.insensitive.n.C1",
+    )
+}
+
+//============================================================================
+#[test]
+fn context_zero_separates_groups() -> TestResult {
+    run(
+        &["-n", "-C0", "-i", "the", BUSTLE, FOX],
+        "tests/expected/bustle.fox.the.insensitive.n.C0",
+    )
+}
+
+//============================================================================
+#[test]
+fn before_context_byte_offset() -> TestResult {
+    run(
+        &["-nb", "-B2", "us", NOBODY],
+        "tests/expected/nobody.txt.us.n.b.B2",
+    )
+}
+
+//============================================================================
+#[test]
+fn after_context_invert_match() -> TestResult {
+    run(
+        &["-vn", "-A1", "you", NOBODY],
+        "tests/expected/nobody.txt.you.invert.n.A1",
+    )
+}
+
+//============================================================================
+#[test]
+fn after_context_overrides_context() -> TestResult {
+    run(
+        &["-n", "-A1", "-C3", "Frog", NOBODY],
+        "tests/expected/nobody.txt.Frog.n.A1.C3",
+    )
+}
+
+//============================================================================
+#[test]
+fn dies_bad_context() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-C", "lots", "foo", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "lots: invalid context length argument",
+        ));
+    Ok(())
+}
+
+//============================================================================
+#[test]
+fn color_always() -> TestResult {
+    run(
+        &["--color=always", "-n", "-i", "the", BUSTLE, NOBODY],
+        "tests/expected/bustle.nobody.the.insensitive.n.color",
+    )
+}
+
+//============================================================================
+#[test]
+fn color_auto_not_a_terminal() -> TestResult {
+    run(
+        &["--color", "-i", "the", BUSTLE, EMPTY, FOX, NOBODY],
+        "tests/expected/all.the.lowercase.insensitive",
+    )
+}
+
+//============================================================================
+#[test]
+fn color_never() -> TestResult {
+    run(
+        &["--colour=never", "-i", "the", BUSTLE, EMPTY, FOX, NOBODY],
+        "tests/expected/all.the.lowercase.insensitive",
+    )
+}
+
+//============================================================================
+#[test]
+fn grep_colors() -> TestResult {
+    let expected = fs::read_to_string("tests/expected/fox.txt.fox.n.grep_colors")?;
+    Command::cargo_bin(PRG)?
+        .args(["--color=always", "-n", "fox", FOX, EMPTY])
+        .env("GREP_COLORS", "ms=04;32:fn=34:ne:ln=")
+        .assert()
+        .stdout(expected);
+    Ok(())
+}
+
+//============================================================================
+#[test]
+fn only_matching() -> TestResult {
+    run(
+        &["-o", "-n", "-b", "o", FOX, NOBODY],
+        "tests/expected/fox.nobody.o.only_matching.n.b",
+    )
+}
+
+//============================================================================
+#[test]
+fn multiple_patterns() -> TestResult {
+    run(
+        &["-e", "the", "-e", "fox", "-e", "-x", BUSTLE, FOX],
+        "tests/expected/bustle.fox.e.the.e.fox",
+    )
+}
+
+//============================================================================
+#[test]
+fn pattern_file() -> TestResult {
+    run(
+        &["-f", PATTERNS, "-e", "death", BUSTLE, FOX],
+        "tests/expected/bustle.fox.f.patterns.e.death",
+    )
+}
+
+//============================================================================
+#[test]
+fn positional_pattern_is_file_with_regexp() -> TestResult {
+    // FOX isn't searched for, it's the first file
+    Command::cargo_bin(PRG)?
+        .args(["-e", "quick", FOX, BUSTLE])
+        .assert()
+        .stdout("tests/inputs/fox.txt:The quick brown fox jumps over the lazy dog.\n");
+    Ok(())
+}
+
+//============================================================================
+#[test]
+fn dies_bad_pattern_file() -> TestResult {
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args(["-f", &bad, FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::is_match(format!(
+            "{bad}: .* [(]os error 2[)]"
+        ))?);
+    Ok(())
+}
+
+//============================================================================
+#[test]
+fn fixed_strings() -> TestResult {
+    run(
+        &[
+            "-F", "-o", "-e", ".", "-e", "the", "-e", "they", BUSTLE, FOX,
+        ],
+        "tests/expected/bustle.fox.F.o.the.they",
+    )
+}
+
+//============================================================================
+#[test]
+fn word_regexp() -> TestResult {
+    run(
+        &["-w", "-i", "-n", "the", BUSTLE],
+        "tests/expected/bustle.txt.the.w.i.n",
+    )
+}
+
+//============================================================================
+#[test]
+fn word_regexp_longer_alternative() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-w", "ab|abc"])
+        .write_stdin("abc\nab\nabcd\n")
+        .assert()
+        .success()
+        .stdout("abc\nab\n");
+    Ok(())
+}
+
+//============================================================================
+#[test]
+fn line_regexp() -> TestResult {
+    run(
+        &["-x", "-e", "Until eternity.", "-e", "Until", BUSTLE],
+        "tests/expected/bustle.txt.x.until",
+    )
+}
+
+//============================================================================
+#[test]
+fn binary_file_matches() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["fox", IMAGE, LATIN1])
+        .assert()
+        .success()
+        .stdout(
+            &b"Binary file tests/binary/image.bin matches\n\
+               tests/binary/latin1.txt:the fox and the caf\xe9\n"[..],
+        );
+    Ok(())
+}
+
+//============================================================================
+#[test]
+fn binary_file_text() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-a", "-n", "quick|header", IMAGE])
+        .assert()
+        .success()
+        .stdout(&b"1:PNG\0\0\x01\xff header\n2:the quick fox\n"[..]);
+    Ok(())
+}
+
+//============================================================================
+#[test]
+fn binary_file_skipped() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-I", "-c", "the", IMAGE, LATIN1])
+        .assert()
+        .success()
+        .stdout("tests/binary/latin1.txt:1\n");
+    Ok(())
+}
+
+//============================================================================
+#[test]
+fn non_utf8_lines() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-n", "-i", "CAF", LATIN1])
+        .assert()
+        .success()
+        .stdout(&b"1:caf\xe9 au lait\n2:the fox and the caf\xe9\n"[..]);
+    Ok(())
+}
+
+//============================================================================
+#[test]
+fn non_utf8_any_byte() -> TestResult {
+    // `.` takes the Latin-1 é as a single byte, in the lines and with --mmap
+    for args in [&["caf.$", LATIN1][..], &["--mmap", "caf.$", LATIN1]] {
+        Command::cargo_bin(PRG)?
+            .args(args)
+            .assert()
+            .success()
+            .stdout(&b"the fox and the caf\xe9\n"[..]);
+    }
+    Ok(())
+}
+
+//============================================================================
+#[test]
+fn recursive_include_exclude_sorted() -> TestResult {
+    run(
+        &[
+            "-rc",
+            "--sort=path",
+            "-j",
+            "4",
+            "--include=*.txt",
+            "--exclude=empty.txt",
+            "--exclude-dir=expected",
+            "the",
+            "tests",
+        ],
+        "tests/expected/the.tests.sort.include.count",
+    )
+}
+
+//============================================================================
+#[test]
+fn dies_bad_threads() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-j", "0", "foo", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("0: invalid number of threads"));
+    Ok(())
+}
+
+//============================================================================
+#[test]
+fn exit_codes() -> TestResult {
+    Command::cargo_bin(PRG)?.args(["fox", FOX]).assert().code(0);
+    Command::cargo_bin(PRG)?.args(["cat", FOX]).assert().code(1);
+    Command::cargo_bin(PRG)?
+        .args(["fox", &gen_bad_file(), FOX])
+        .assert()
+        .code(2);
+    Command::cargo_bin(PRG)?.assert().code(2);
+    Ok(())
+}
+
+//============================================================================
+#[test]
+fn quiet() -> TestResult {
+    // A match is a success even with the missing file
+    Command::cargo_bin(PRG)?
+        .args(["-q", "fox", &gen_bad_file(), FOX])
+        .assert()
+        .code(0)
+        .stdout("");
+    Command::cargo_bin(PRG)?
+        .args(["-q", "cat", FOX])
+        .assert()
+        .code(1)
+        .stdout("");
+    Ok(())
+}
+
+//============================================================================
+#[test]
+fn no_messages() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-s", "fox", &gen_bad_file(), FOX])
+        .assert()
+        .code(2)
+        .stderr("")
+        .stdout(predicate::str::contains("lazy dog"));
+    Ok(())
+}
+
+//============================================================================
+#[test]
+fn files_with_matches() -> TestResult {
+    run(
+        &["-l", "-i", "the", BUSTLE, EMPTY, FOX, NOBODY],
+        "tests/expected/the.files_with_matches",
+    )
+}
+
+//============================================================================
+#[test]
+fn files_without_match() -> TestResult {
+    run(
+        &["-L", "-i", "dog", BUSTLE, EMPTY, FOX, NOBODY],
+        "tests/expected/dog.files_without_match",
+    )
+}
+
+//============================================================================
+#[test]
+fn max_count() -> TestResult {
+    run(
+        &["-m", "1", "-n", "-A", "1", "the", BUSTLE, NOBODY],
+        "tests/expected/bustle.nobody.the.m1.n.A1",
+    )
+}
+
+//============================================================================
+#[test]
+fn mmap() -> TestResult {
+    run(
+        &["--mmap", "-w", "-i", "-n", "the", BUSTLE],
+        "tests/expected/bustle.txt.the.w.i.n",
+    )?;
+    run(
+        &["--mmap", "-c", "The", BUSTLE],
+        "tests/expected/bustle.txt.the.capitalized.count",
+    )?;
+    run(
+        &["--mmap", "-n", "-C1", "-i", "the", BUSTLE, NOBODY],
+        "tests/expected/bustle.nobody.the.insensitive.n.C1",
+    )
+}
@@ -0,0 +1,28 @@
+tests/inputs/fox.txt:1:12:o
+tests/inputs/fox.txt:1:17:o
+tests/inputs/fox.txt:1:26:o
+tests/inputs/fox.txt:1:41:o
+tests/inputs/nobody.txt:1:5:o
+tests/inputs/nobody.txt:1:7:o
+tests/inputs/nobody.txt:1:14:o
+tests/inputs/nobody.txt:1:21:o
+tests/inputs/nobody.txt:2:31:o
+tests/inputs/nobody.txt:2:37:o
+tests/inputs/nobody.txt:2:39:o
+tests/inputs/nobody.txt:2:46:o
+tests/inputs/nobody.txt:2:47:o
+tests/inputs/nobody.txt:3:71:o
+tests/inputs/nobody.txt:4:80:o
+tests/inputs/nobody.txt:4:111:o
+tests/inputs/nobody.txt:4:116:o
+tests/inputs/nobody.txt:6:124:o
+tests/inputs/nobody.txt:6:137:o
+tests/inputs/nobody.txt:6:145:o
+tests/inputs/nobody.txt:6:149:o
+tests/inputs/nobody.txt:7:156:o
+tests/inputs/nobody.txt:7:177:o
+tests/inputs/nobody.txt:8:185:o
+tests/inputs/nobody.txt:8:192:o
+tests/inputs/nobody.txt:8:214:o
+tests/inputs/nobody.txt:9:228:o
+tests/inputs/nobody.txt:9:243:o
@@ -9,13 +9,19 @@ pub struct Argument {
 
     #[arg(help = "The output file of the program")]
     pub output: PathBuf,
+
+    #[arg(
+        long,
+        help = "The seed of the layers, which are the same for the same seed [default: random]"
+    )]
+    pub seed: Option<u64>,
 }
 fn main() {
     // i assume we don't need to use clap for this exercise
     let arg = Argument::parse();
 
-    let mut thread_rng = rand::thread_rng();
-    let layers = point_layer::gen_layer_list(&mut thread_rng, arg.n);
+    let mut rng = point_layer::seeded_rng(arg.seed.unwrap_or_else(rand::random));
+    let layers = point_layer::gen_layer_list(&mut rng, arg.n);
 
     let mut output_file =
         std::fs::File::create(arg.output).expect("failed to create an output file");
@@ -45,7 +45,7 @@ fn test_valid() {
 
         // test color part
         assert_eq!(parts[1].trim().len(), 9); // #FFFFFFFF 9 characters in total
-        assert_eq!(parts[1].trim().chars().nth(0).unwrap(), '#');
+        assert_eq!(parts[1].trim().chars().next().unwrap(), '#');
 
         for point_index in 0..(parts.len() - 2) / 2 {
             let x_index = point_index * 2 + 2;
@@ -57,3 +57,26 @@ fn test_valid() {
         }
     }
 }
+
+#[test]
+fn test_seed() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let out_file_path = temp_dir.path().join("output.csv");
+    let expected = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/expected_seed_42.csv");
+
+    let mut cmd = Command::cargo_bin("layer").unwrap();
+    cmd.arg("3").arg(&out_file_path).args(["--seed", "42"]);
+    cmd.assert().success();
+
+    assert_eq!(
+        std::fs::read_to_string(&out_file_path).unwrap(),
+        std::fs::read_to_string(expected).unwrap()
+    );
+
+    // not a number
+    let mut cmd = Command::cargo_bin("layer").unwrap();
+    cmd.arg("3")
+        .arg(&out_file_path)
+        .args(["--seed", "forty-two"]);
+    cmd.assert().failure();
+}
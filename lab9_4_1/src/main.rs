@@ -8,14 +8,19 @@ pub struct Argument {
     pub color: String,
     #[arg(help = "the output file of the svg output")]
     pub output_file: PathBuf,
+    #[arg(
+        long,
+        help = "The seed of the layers, which are the same for the same seed [default: random]"
+    )]
+    pub seed: Option<u64>,
 }
 
 fn main() {
     let argument = Argument::parse();
 
-    let mut thread_rng = rand::thread_rng();
+    let mut rng = point_layer::seeded_rng(argument.seed.unwrap_or_else(rand::random));
 
-    let layer = point_layer::gen_layer("MY LAYERR".into(), "#00FF00FF".into(), &mut thread_rng);
+    let layer = point_layer::gen_layer("MY LAYERR".into(), "#00FF00FF".into(), &mut rng);
 
     let output_file =
         std::fs::File::create(argument.output_file).expect("failed to create an output file");
@@ -0,0 +1,21 @@
+use std::path::PathBuf;
+
+use assert_cmd::Command;
+
+#[test]
+fn test_seed() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let out_file_path = temp_dir.path().join("output.svg");
+    let expected = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/expected_seed_42.svg");
+
+    let mut cmd = Command::cargo_bin("lab9_4_1").unwrap();
+    cmd.arg("#00FF00FF")
+        .arg(&out_file_path)
+        .args(["--seed", "42"]);
+    cmd.assert().success();
+
+    assert_eq!(
+        std::fs::read_to_string(&out_file_path).unwrap(),
+        std::fs::read_to_string(expected).unwrap()
+    );
+}
@@ -8,14 +8,19 @@ pub struct Argument {
     pub layer_count: usize,
     #[arg(help = "the output file of the svg output")]
     pub output_file: PathBuf,
+    #[arg(
+        long,
+        help = "The seed of the layers, which are the same for the same seed [default: random]"
+    )]
+    pub seed: Option<u64>,
 }
 
 fn main() {
     let argument = Argument::parse();
 
-    let mut thread_rng = rand::thread_rng();
+    let mut rng = point_layer::seeded_rng(argument.seed.unwrap_or_else(rand::random));
 
-    let layer = point_layer::gen_layer_list(&mut thread_rng, argument.layer_count);
+    let layer = point_layer::gen_layer_list(&mut rng, argument.layer_count);
 
     let output_file =
         std::fs::File::create(argument.output_file).expect("failed to create an output file");
@@ -1,4 +1,14 @@
-use rand::Rng;
+//! Random layers of points.
+//!
+//! The layers are drawn from the raw `u32`s and `u64`s of the generator instead of `rand`'s
+//! distributions, which may change between `rand` versions, so the layers of a [`seeded_rng`]
+//! are the same for the same seed on every platform. The stream that's guaranteed is ChaCha8
+//! from [`seeded_rng`], read by [`gen_layer_list`] as, for each layer, a `u32` each for the red,
+//! green, blue and alpha of its colour, then what [`gen_layer`] reads: a `u32` for the number
+//! of points, then a `u64` each for the x and y of every point.
+
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub struct Point {
@@ -13,17 +23,41 @@ pub struct Layer {
     pub points: Vec<Point>,
 }
 
-pub fn gen_layer(name: String, color: String, rng: &mut impl Rng) -> Layer {
+/// The generator of the stable layers, which starts with the seed in little-endian followed by
+/// zeros as its key.
+pub fn seeded_rng(seed: u64) -> ChaCha8Rng {
+    let mut key = [0; 32];
+    key[..8].copy_from_slice(&seed.to_le_bytes());
+
+    ChaCha8Rng::from_seed(key)
+}
+
+/// A number in `min..=max` from the high bits of a `u32`, which is a little biased for the
+/// ranges that don't divide 2^32.
+fn gen_int(rng: &mut impl RngCore, min: u32, max: u32) -> u32 {
+    let span = u64::from(max - min) + 1;
+
+    min + ((u64::from(rng.next_u32()) * span) >> 32) as u32
+}
+
+/// A number in `min..=max` from the high 53 bits of a `u64`.
+fn gen_float(rng: &mut impl RngCore, min: f64, max: f64) -> f64 {
+    let unit = (rng.next_u64() >> 11) as f64 / ((1u64 << 53) - 1) as f64;
+
+    min + (max - min) * unit
+}
+
+pub fn gen_layer(name: String, color: String, rng: &mut impl RngCore) -> Layer {
     let mut layer = Layer {
         name,
         color,
         points: Vec::new(),
     };
-    let count = rng.gen_range(20..=50);
+    let count = gen_int(rng, 20, 50);
 
     for _ in 0..count {
-        let x = rng.gen_range(-100.0..=100.0);
-        let y = rng.gen_range(-100.0..=100.0);
+        let x = gen_float(rng, -100.0, 100.0);
+        let y = gen_float(rng, -100.0, 100.0);
 
         layer.points.push(Point { x, y });
     }
@@ -31,14 +65,14 @@ pub fn gen_layer(name: String, color: String, rng: &mut impl Rng) -> Layer {
     layer
 }
 
-pub fn gen_layer_list(rng: &mut impl Rng, n: usize) -> Vec<Layer> {
+pub fn gen_layer_list(rng: &mut impl RngCore, n: usize) -> Vec<Layer> {
     let mut layers = Vec::new();
     for i in 0..n {
         let layer_name = format!("Layer {i}");
-        let r = rng.gen_range(0..=255);
-        let g = rng.gen_range(0..=255);
-        let b = rng.gen_range(0..=255);
-        let a = rng.gen_range(0..=255);
+        let r = gen_int(rng, 0, 255);
+        let g = gen_int(rng, 0, 255);
+        let b = gen_int(rng, 0, 255);
+        let a = gen_int(rng, 0, 255);
         let layer_color = format!("#{:02X}{:02X}{:02X}{:02X}", r, g, b, a);
 
         layers.push(gen_layer(layer_name, layer_color, rng));
@@ -77,4 +111,24 @@ mod tests {
             assert!(layer.color.starts_with('#'))
         }
     }
+
+    #[test]
+    fn seeded_rng_test() {
+        let layers = super::gen_layer_list(&mut super::seeded_rng(42), 3);
+
+        assert_eq!(layers, super::gen_layer_list(&mut super::seeded_rng(42), 3));
+        assert_ne!(layers, super::gen_layer_list(&mut super::seeded_rng(43), 3));
+
+        // the first layer of the stable stream
+        let first = &layers[0];
+        assert_eq!(first.color, "#19591649");
+        assert_eq!(first.points.len(), 32);
+        assert_eq!(
+            first.points[0],
+            super::Point {
+                x: 17.81910520482215,
+                y: 17.59962897598244
+            }
+        );
+    }
 }
@@ -26,3 +26,212 @@ pub fn save_layers<'a>(layers: impl Iterator<Item = &'a Layer>, mut output_file:
 
     writeln!(output_file, "</svg>").expect("failed to write to the output file")
 }
+
+/// The size of the square picture that the points are drawn in.
+pub const SIZE: f64 = 500.;
+/// The radius of the points.
+pub const POINT_RADIUS: f64 = 50.;
+pub const AXIS_COLOR: &str = "#888888";
+
+/// The area of the plane that's drawn, which is square so the picture isn't stretched.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Bounds {
+    pub min_x: f64,
+    pub min_y: f64,
+    pub size: f64,
+}
+
+impl Bounds {
+    /// Fits all the points, with a margin of a tenth of their extent on every side.
+    pub fn fit(points: impl IntoIterator<Item = (f64, f64)>) -> Self {
+        let (mut min_x, mut min_y) = (f64::INFINITY, f64::INFINITY);
+        let (mut max_x, mut max_y) = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+
+        for (x, y) in points {
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+        }
+
+        if min_x > max_x {
+            return Self {
+                min_x: -1.,
+                min_y: -1.,
+                size: 2.,
+            };
+        }
+
+        // a single point is in the middle of the same area as no points
+        let extent = (max_x - min_x).max(max_y - min_y);
+        let size = if extent > 0. { extent * 1.2 } else { 2. };
+
+        Self {
+            min_x: (min_x + max_x - size) / 2.,
+            min_y: (min_y + max_y - size) / 2.,
+            size,
+        }
+    }
+
+    /// Where the point is in the picture, the y axis of which points down.
+    pub fn to_picture(&self, x: f64, y: f64) -> (f64, f64) {
+        (
+            (x - self.min_x) / self.size * SIZE,
+            SIZE - (y - self.min_y) / self.size * SIZE,
+        )
+    }
+
+    /// Where the axes cross in the picture, at the origin or at the closest edge to it.
+    pub fn axes(&self) -> (f64, f64) {
+        let x = 0f64.clamp(self.min_x, self.min_x + self.size);
+        let y = 0f64.clamp(self.min_y, self.min_y + self.size);
+
+        self.to_picture(x, y)
+    }
+}
+
+/// The area of the plane that `point_layer` generates the layers in.
+pub const LAYER_BOUNDS: Bounds = Bounds {
+    min_x: -100.,
+    min_y: -100.,
+    size: 200.,
+};
+
+/// Splits a `#RRGGBBAA` colour into `#RRGGBB` and its opacity, for the SVG viewers that don't
+/// read the alpha from the colour.
+fn fill(color: &str) -> (&str, f64) {
+    match color.get(7..9).map(|x| u8::from_str_radix(x, 16)) {
+        Some(Ok(alpha)) if color.len() == 9 => (&color[..7], f64::from(alpha) / 255.),
+        _ => (color, 1.),
+    }
+}
+
+/// Draws a point of `save_points`, as big as the ones of `save_layers`.
+fn write_circle(
+    output: &mut impl std::io::Write,
+    bounds: &Bounds,
+    x: f64,
+    y: f64,
+    color: &str,
+) -> std::io::Result<()> {
+    let (cx, cy) = bounds.to_picture(x, y);
+    let (fill, opacity) = fill(color);
+
+    writeln!(
+        output,
+        "\t<circle cx=\"{cx}\" cy=\"{cy}\" r=\"{POINT_RADIUS}\" fill=\"{fill}\" fill-opacity=\"{opacity}\" />"
+    )
+}
+
+/// Draws the points with their colours like `save_layers` does, in the bounds with the axes and
+/// the extent of the bounds written at their ends.
+pub fn save_points<'a>(
+    points: impl IntoIterator<Item = (f64, f64, &'a str)>,
+    bounds: &Bounds,
+    mut output: impl std::io::Write,
+) -> std::io::Result<()> {
+    writeln!(output, "{SVG_HEADER}")?;
+    writeln!(output, "{SVG_RECT}")?;
+
+    let (axis_x, axis_y) = bounds.axes();
+    writeln!(
+        output,
+        "\t<line x1=\"0\" y1=\"{axis_y}\" x2=\"{SIZE}\" y2=\"{axis_y}\" stroke=\"{AXIS_COLOR}\" />"
+    )?;
+    writeln!(
+        output,
+        "\t<line x1=\"{axis_x}\" y1=\"0\" x2=\"{axis_x}\" y2=\"{SIZE}\" stroke=\"{AXIS_COLOR}\" />"
+    )?;
+
+    let labels = [
+        (2., axis_y - 4., "start", bounds.min_x),
+        (SIZE - 2., axis_y - 4., "end", bounds.min_x + bounds.size),
+        (axis_x + 4., SIZE - 4., "start", bounds.min_y),
+        (axis_x + 4., 12., "start", bounds.min_y + bounds.size),
+    ];
+    for (x, y, anchor, value) in labels {
+        writeln!(
+            output,
+            "\t<text x=\"{x}\" y=\"{y}\" font-size=\"10\" text-anchor=\"{anchor}\" fill=\"{AXIS_COLOR}\">{value:.2}</text>"
+        )?;
+    }
+
+    for (x, y, color) in points {
+        write_circle(&mut output, bounds, x, y, color)?;
+    }
+
+    writeln!(output, "</svg>")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{save_points, Bounds, LAYER_BOUNDS, SIZE};
+
+    #[test]
+    fn bounds_fit_test() {
+        // a margin of a tenth around the longer side, centred on the shorter one
+        let bounds = Bounds::fit([(0., 0.), (10., 5.)]);
+        assert_eq!(
+            bounds,
+            Bounds {
+                min_x: -1.,
+                min_y: -3.5,
+                size: 12.
+            }
+        );
+
+        assert_eq!(
+            Bounds::fit([]),
+            Bounds {
+                min_x: -1.,
+                min_y: -1.,
+                size: 2.
+            }
+        );
+
+        // a single point still has an area around it
+        let bounds = Bounds::fit([(3., 4.)]);
+        assert_eq!(bounds.size, 2.);
+        assert_eq!(bounds.to_picture(3., 4.), (SIZE / 2., SIZE / 2.));
+    }
+
+    #[test]
+    fn to_picture_test() {
+        assert_eq!(LAYER_BOUNDS.to_picture(-100., -100.), (0., SIZE));
+        assert_eq!(LAYER_BOUNDS.to_picture(100., 100.), (SIZE, 0.));
+        assert_eq!(LAYER_BOUNDS.to_picture(0., 50.), (250., 125.));
+    }
+
+    #[test]
+    fn axes_test() {
+        assert_eq!(LAYER_BOUNDS.axes(), (250., 250.));
+
+        // the origin is left of and above the bounds, so the axes are on their edges
+        let bounds = Bounds {
+            min_x: 1.,
+            min_y: -10.,
+            size: 5.,
+        };
+        assert_eq!(bounds.axes(), (0., 0.));
+    }
+
+    #[test]
+    fn save_points_test() {
+        let mut output = Vec::new();
+        save_points(
+            [(0., 0., "#FF000080"), (-100., 100., "#00FF00")],
+            &LAYER_BOUNDS,
+            &mut output,
+        )
+        .unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.contains(
+            "\t<circle cx=\"250\" cy=\"250\" r=\"50\" fill=\"#FF0000\" fill-opacity=\"0.5019607843137255\" />\n"
+        ));
+        assert!(output.contains(
+            "\t<circle cx=\"0\" cy=\"0\" r=\"50\" fill=\"#00FF00\" fill-opacity=\"1\" />\n"
+        ));
+        assert!(output.ends_with("</svg>\n"));
+    }
+}